@@ -0,0 +1,296 @@
+use crate::config::{Config, Mode, RunError, Source};
+use crate::diagnostic;
+use crate::list::ListFormat;
+use crate::paths::{find_program, relative_path};
+use crate::runner::{RunnerCommand, RunnerOptions};
+use crate::tags::TagFilter;
+use anyhow::bail;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+pub struct Problem {
+    pub severity: Severity,
+    pub message: String,
+    pub source: Option<Source>,
+}
+
+#[derive(Serialize)]
+struct ProblemItem<'a> {
+    severity: Severity,
+    message: &'a str,
+    file: Option<PathBuf>,
+    line: Option<usize>,
+}
+
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(&mut self, severity: Severity, message: String, source: Option<&Source>) {
+        self.0.push(Problem {
+            severity,
+            message,
+            source: source.cloned(),
+        });
+    }
+
+    /// Pushes an error, located in the config file when it has been raised by a run.
+    fn push_error(&mut self, err: &anyhow::Error) {
+        match err.chain().find_map(|err| err.downcast_ref::<RunError>()) {
+            Some(located) => self.push(
+                Severity::Error,
+                format!("{:#}", located.error),
+                Some(&located.source),
+            ),
+            None => self.push(Severity::Error, format!("{err:#}"), None),
+        }
+    }
+
+    fn errors(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|problem| problem.severity == Severity::Error)
+            .count()
+    }
+}
+
+/// Validates the config without running anything, and reports all the problems found at once:
+/// the errors prevent the commands from running (e.g. a program which is not installed on this
+/// machine), the warnings are about the commands which may not behave as expected (e.g. two
+/// commands with the same name). Fails when at least one error is found.
+pub fn check(config: Config, warnings: Vec<String>, format: &ListFormat) -> anyhow::Result<()> {
+    let mut problems = Problems::default();
+    for warning in warnings {
+        problems.push(Severity::Warning, warning, None);
+    }
+    check_config(config, &mut problems);
+    report(&problems, format)
+}
+
+/// Collects the problems of a config which loaded, e.g. to report them as the diagnostics of the
+/// language server.
+pub fn problems(config: Config) -> Vec<Problem> {
+    let mut problems = Problems::default();
+    check_config(config, &mut problems);
+    problems.0
+}
+
+/// Reports a config which failed to load (e.g. a syntax error, an unknown key or a missing
+/// profile) as an error, located in the file when possible.
+pub fn load_error(err: &anyhow::Error, format: &ListFormat) -> anyhow::Result<()> {
+    let mut problems = Problems::default();
+    problems.push_error(err);
+    report(&problems, format)
+}
+
+/// Prints the problems, and fails when at least one of them is an error.
+fn report(problems: &Problems, format: &ListFormat) -> anyhow::Result<()> {
+    let cwd = std::env::current_dir()?;
+    match format {
+        ListFormat::Json => {
+            let items: Vec<_> = problems
+                .0
+                .iter()
+                .map(|problem| ProblemItem {
+                    severity: problem.severity,
+                    message: &problem.message,
+                    file: problem
+                        .source
+                        .as_ref()
+                        .map(|source| relative_path(&source.path, &cwd)),
+                    line: problem.source.as_ref().and_then(line_number),
+                })
+                .collect();
+            serde_json::to_writer_pretty(std::io::stdout(), &items)?;
+            println!();
+        }
+        ListFormat::Table => {
+            for problem in &problems.0 {
+                eprintln!("{}: {}", problem.severity, render(problem));
+            }
+        }
+    }
+
+    match problems.errors() {
+        0 => Ok(()),
+        1 => bail!("the check failed with 1 error"),
+        errors => bail!("the check failed with {errors} errors"),
+    }
+}
+
+/// Reports the problems of the config one by one, and then the error of the runner if they do not
+/// explain it, so that the config passes the check only if it can be run.
+fn check_config(config: Config, problems: &mut Problems) {
+    let errors = problems.errors();
+    let resolved = RunnerOptions::try_from(config.clone());
+    if let Ok(workdir) = config.resolve_workdir() {
+        check_runs(config, &workdir, problems);
+    }
+    if let Err(err) = resolved {
+        if problems.errors() == errors {
+            problems.push_error(&err);
+        }
+    }
+}
+
+fn check_runs(mut config: Config, workdir: &Path, problems: &mut Problems) {
+    // the invalid global and tag variables are reported once, and left out of the runs
+    config.envs = check_envs(std::mem::take(&mut config.envs), None, problems);
+    for tag in config.tag_options.values_mut() {
+        tag.tag_envs = check_envs(std::mem::take(&mut tag.tag_envs), None, problems);
+    }
+
+    let runs = std::mem::take(&mut config.runs);
+    let mut commands: Vec<RunnerCommand> = vec![];
+    for mut run in runs {
+        let source = run.command_source.clone();
+        run.command_envs = check_envs(run.command_envs, source.as_ref(), problems);
+
+        match run.try_into_runner_command(&config, workdir) {
+            Ok(command) => {
+                // the runs are named after their program by default
+                if commands.iter().any(|other| other.name == command.name) {
+                    problems.push(
+                        Severity::Warning,
+                        format!(
+                            "the name `{}` is already used by another command",
+                            command.name
+                        ),
+                        source.as_ref(),
+                    );
+                }
+                check_program(&command, problems);
+                commands.push(command);
+            }
+            Err(err) => problems.push(Severity::Error, format!("{err:#}"), source.as_ref()),
+        }
+    }
+
+    check_tags(&config, &commands, problems);
+
+    if let Some(Mode::Tmux) = config.mode {
+        let program = config.tmux.tmux_program.as_deref().unwrap_or("tmux");
+        if find_program(Path::new(program), std::env::var_os("PATH").as_deref()).is_none() {
+            problems.push(
+                Severity::Error,
+                format!("the tmux mode requires tmux, but `{program}` was not found on the PATH"),
+                None,
+            );
+        }
+    }
+}
+
+/// Reports the invalid variables, and returns the valid ones.
+fn check_envs(envs: Vec<String>, source: Option<&Source>, problems: &mut Problems) -> Vec<String> {
+    envs.into_iter()
+        .filter(|kv| {
+            let Some((key, _)) = kv.split_once('=') else {
+                problems.push(
+                    Severity::Error,
+                    format!("invalid environment variable: {kv}"),
+                    source,
+                );
+                return false;
+            };
+            if key.is_empty() {
+                problems.push(
+                    Severity::Error,
+                    format!("invalid environment variable: {kv}, the name is empty"),
+                    source,
+                );
+                return false;
+            }
+            let is_portable = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !is_portable {
+                problems.push(
+                    Severity::Warning,
+                    format!("the environment variable `{key}` may not be readable by all the programs, as its name is not made of letters, digits and underscores"),
+                    source,
+                );
+            }
+            true
+        })
+        .collect()
+}
+
+/// Reports the programs which cannot be found, as the runs spawning them would fail.
+fn check_program(command: &RunnerCommand, problems: &mut Problems) {
+    let program = &command.program;
+    let found = command.find_program().is_some();
+    if !found {
+        problems.push(
+            Severity::Error,
+            format!(
+                "the program `{program}` was not found, it may not be installed on this machine"
+            ),
+            command.source.as_ref(),
+        );
+    }
+}
+
+/// Reports the tags of the filter which are carried by none of the commands, as they are most
+/// likely typos.
+fn check_tags(config: &Config, commands: &[RunnerCommand], problems: &mut Problems) {
+    let filter = match TagFilter::parse(
+        config.tags.as_deref().unwrap_or_default(),
+        config.exclude_tags.as_deref().unwrap_or_default(),
+    ) {
+        Ok(filter) => filter,
+        Err(err) => {
+            problems.push(Severity::Error, format!("{err:#}"), None);
+            return;
+        }
+    };
+
+    let mut reported: Vec<&str> = vec![];
+    for tag in filter.tags() {
+        if reported.contains(&tag) || commands.iter().any(|cmd| cmd.tags.iter().any(|t| t == tag)) {
+            continue;
+        }
+        reported.push(tag);
+        problems.push(
+            Severity::Error,
+            format!("the `{tag}` tag is not carried by any command"),
+            None,
+        );
+    }
+}
+
+fn render(problem: &Problem) -> String {
+    let Some(source) = &problem.source else {
+        return problem.message.clone();
+    };
+    match std::fs::read_to_string(&source.path) {
+        Ok(original) => diagnostic::render(
+            &problem.message,
+            &source.path,
+            &original,
+            source.span.clone(),
+        ),
+        Err(_) => problem.message.clone(),
+    }
+}
+
+fn line_number(source: &Source) -> Option<usize> {
+    let original = std::fs::read_to_string(&source.path).ok()?;
+    let before = original.get(..source.span.start)?;
+    Some(before.matches('\n').count() + 1)
+}
@@ -1,10 +1,14 @@
+use crate::completions::CompletionShell;
 use crate::config::Config;
-use clap::Parser;
+use crate::exporters::Exporter;
+use crate::list::{ListFormat, ListGroupBy};
+use clap::{ArgGroup, ArgMatches, Parser};
 use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "run")]
 #[command(version)]
+#[command(subcommand_value_name = "SUBCOMMAND")]
 #[command(about = "
 Run is a task runner.
 
@@ -14,40 +18,176 @@ You can pass commands directly for simple tasks:
 Or you can use config files for more complex setups:
     $ run -f dev.toml
 
+The names of the subcommands are reserved, pass the commands named like one after --:
+    $ run -- 'man ls'
+
 For more information: https://run-cli.org")]
+#[command(group(ArgGroup::new("formatted").args(["command_check", "command_list"]).multiple(true)))]
+#[allow(clippy::struct_excessive_bools)] // flags
 pub struct Cli {
     #[arg(
         short,
         long = "file",
-        help = "Specify the config file to load (default is to load run.toml in the current directory, unless at least one COMMAND is passed)",
+        help = "Specify the config file to load (default is to load the first of run.toml, run.yaml, run.yml, run.json or Procfile found in the current directory, unless at least one COMMAND is passed)",
         value_name = "FILE"
     )]
     pub file: Option<PathBuf>,
 
     #[arg(
-        help = "Append a command to run. Can be called multiple times. Providing at least one command will prevent the default config file from being loaded",
+        help = "Append a command to run. Can be called multiple times. Providing at least one command will prevent the default config file from being loaded. The commands named like a subcommand (e.g. init or man) must be passed after --",
         value_name = "COMMAND"
     )]
     pub commands: Vec<String>,
 
+    #[arg(
+        short,
+        long = "name",
+        help = "Only run the runs of the given name, among the ones matching the tags. Can be called multiple times",
+        value_name = "NAME"
+    )]
+    pub names: Vec<String>,
+
     #[command(flatten)]
     pub config: Config,
 
+    #[arg(
+        long = "no-user-config",
+        help = "Do not load the user config ($XDG_CONFIG_HOME/run/config.toml, or ~/.config/run/config.toml)"
+    )]
+    pub no_user_config: bool,
+
+    #[arg(
+        long = "set",
+        help = "Override a key of the config by its dotted path, with a TOML value, kept as is for the string keys (e.g. tmux.session_prefix=api- or run.web.env.PORT=4000). Can be called multiple times",
+        value_name = "KEY=VALUE"
+    )]
+    pub sets: Vec<String>,
+
+    #[command(subcommand)]
+    pub subcommand: Option<Subcommand>,
+
     #[arg(
         long = "check",
-        help = "Start in check mode to validate the config and exit"
+        help = "Validate the config, report all the errors and warnings found and exit"
     )]
     pub command_check: bool,
 
+    #[arg(
+        long = "list",
+        help = "Print the commands matching the tags, with their description, and exit"
+    )]
+    pub command_list: bool,
+
+    #[arg(
+        long = "format",
+        value_enum,
+        default_value_t,
+        requires = "formatted",
+        help = "Change the output format of --list and --check"
+    )]
+    pub list_format: ListFormat,
+
+    #[arg(
+        long = "group-by",
+        value_enum,
+        default_value_t,
+        requires = "command_list",
+        help = "Group the commands printed by --list in the table format"
+    )]
+    pub list_group_by: ListGroupBy,
+
     #[arg(
         long = "print-options",
         help = "Print the resolved options on stdout and exit"
     )]
     pub command_print_options: bool,
+
+    #[arg(
+        long = "print-schema",
+        help = "Print the JSON Schema of the config file format on stdout and exit"
+    )]
+    pub command_print_schema: bool,
+
+    #[arg(
+        long = "update-docs",
+        help = "Execute the commands of a literate config file, write their stdout in ```output code blocks right after them and exit"
+    )]
+    pub command_update_docs: bool,
+}
+
+#[derive(clap::Subcommand)]
+pub enum Subcommand {
+    #[command(about = "Print the completion script of the given shell on stdout")]
+    Completions { shell: CompletionShell },
+
+    #[command(
+        about = "Print the resolved options of the commands matching the tags, and where each of them comes from",
+        after_help = "The line of each option is only known for TOML config files, literate or not. For YAML and JSON config files, the line of the run is given instead.\n\nTo run a program named explain instead, pass it after --: run -- explain"
+    )]
+    Explain,
+
+    #[command(
+        about = "Export the commands matching the tags to another tool",
+        after_help = "To run a program named export instead, pass it after --: run -- export"
+    )]
+    Export {
+        #[command(subcommand)]
+        exporter: Exporter,
+    },
+
+    #[command(
+        about = "Write a starter config file from the tooling detected in the directory",
+        after_help = "To run a program named init instead, pass it after --: run -- init"
+    )]
+    Init {
+        #[arg(default_value = ".", help = "The directory of the project")]
+        dir: PathBuf,
+
+        #[arg(long, help = "Write a literate config file (run.toml.md)")]
+        literate: bool,
+
+        #[arg(long, help = "Overwrite the config file if it already exists")]
+        force: bool,
+
+        #[arg(long, help = "Print the config file on stdout instead of writing it")]
+        stdout: bool,
+    },
+
+    #[command(
+        about = "Start a language server for the config files, over stdio",
+        after_help = "To run a program named lsp instead, pass it after --: run -- lsp"
+    )]
+    Lsp,
+
+    #[command(
+        about = "Print the man page on stdout",
+        after_help = "To run the man program instead, pass it after --: run -- 'man ls'"
+    )]
+    Man {
+        #[arg(long, help = "Print the reference in markdown instead of roff")]
+        markdown: bool,
+    },
+
+    #[command(
+        hide = true,
+        about = "Print the tags of the config file, used by completions"
+    )]
+    CompleteTags,
+
+    #[command(
+        hide = true,
+        about = "Print the names of the runs of the config file, used by completions"
+    )]
+    CompleteNames,
 }
 
 impl Cli {
-    pub fn parse() -> Self {
-        <Self as clap::Parser>::parse()
+    /// Parses the arguments, also returning the matches which tell where each value comes from
+    /// (a flag, an environment variable or a default).
+    pub fn parse() -> (Self, ArgMatches) {
+        let matches = <Self as clap::CommandFactory>::command().get_matches();
+        let cli = <Self as clap::FromArgMatches>::from_arg_matches(&matches)
+            .unwrap_or_else(|err| err.exit());
+        (cli, matches)
     }
 }
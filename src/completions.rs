@@ -0,0 +1,218 @@
+use crate::cli::Cli;
+use crate::config::{Command, Config};
+use clap::{CommandFactory, ValueEnum, ValueHint};
+use clap_complete::Shell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// The options completed by calling `run`, with the shell function completing them in zsh.
+const DYNAMIC_ARGS: [(&str, &str); 3] = [
+    ("tags", "_run_tags"),
+    ("exclude_tags", "_run_tags"),
+    ("names", "_run_names"),
+];
+
+/// Prints the completion script of `shell`. On top of the static completions generated from the
+/// CLI definition, the values of `--tags` and `--exclude-tags` are completed with the tags found
+/// in the config file, by calling `run complete-tags`, and the values of `--name` with the names
+/// of its runs, by calling `run complete-names`. Both are given the `-f/--file` of the command
+/// line.
+pub fn print_completions(shell: &CompletionShell) {
+    let mut cmd = visible_command();
+    let shell_kind = match shell {
+        CompletionShell::Bash => Shell::Bash,
+        CompletionShell::Zsh => Shell::Zsh,
+        CompletionShell::Fish => Shell::Fish,
+    };
+    let mut script = vec![];
+    clap_complete::generate(shell_kind, &mut cmd, "run", &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+
+    match shell {
+        CompletionShell::Bash => {
+            // the subcommands have options of their own, e.g. the `--name` of `export systemd`
+            let subcommands: Vec<_> = cmd.get_subcommands().map(clap::Command::get_name).collect();
+            let dynamic = BASH_DYNAMIC.replace("SUBCOMMANDS", &subcommands.join("|"));
+            print!("{script}{dynamic}");
+        }
+        // clap_complete has no custom completers for zsh: the dynamic options are given an empty
+        // `( )` action by their value hint, which is then replaced by their function
+        CompletionShell::Zsh => {
+            for (id, function) in DYNAMIC_ARGS {
+                let arg = cmd
+                    .get_arguments()
+                    .find(|arg| arg.get_id() == id)
+                    .expect("the dynamic args are defined by the CLI");
+                for value_name in arg.get_value_names().into_iter().flatten() {
+                    script = script.replace(
+                        &format!(":{value_name}:( )'"),
+                        &format!(":{value_name}:{function}'"),
+                    );
+                }
+            }
+            let (compdef, script) = script.split_once('\n').unwrap_or_default();
+            print!("{compdef}\n{ZSH_DYNAMIC}{script}");
+        }
+        CompletionShell::Fish => print!("{script}{FISH_DYNAMIC}"),
+    }
+}
+
+/// The CLI definition without its hidden subcommands, which are internal (e.g. `complete-tags`).
+fn visible_command() -> clap::Command {
+    let cli = Cli::command();
+    let subcommands = cli
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set());
+    clap::Command::new("run")
+        .version(env!("CARGO_PKG_VERSION"))
+        .args(cli.get_arguments().map(|arg| {
+            if DYNAMIC_ARGS.iter().any(|(id, _)| arg.get_id() == id) {
+                arg.clone().value_hint(ValueHint::Other)
+            } else {
+                arg.clone()
+            }
+        }))
+        .groups(cli.get_groups().cloned())
+        .subcommands(subcommands.cloned())
+}
+
+/// Prints the tags of the given config file (or the default one), one per line. Errors are
+/// ignored, as there is nothing to complete from an invalid config.
+pub async fn print_tags(file: Option<PathBuf>) -> anyhow::Result<()> {
+    let file = file.unwrap_or_else(|| Config::default_path("."));
+    let Ok((config, _)) = Config::load(file).await else {
+        return Ok(());
+    };
+
+    // the runs inherit the tags of their templates
+    let tags: BTreeSet<_> = config
+        .runs
+        .iter()
+        .chain(config.templates.values())
+        .chain(
+            config
+                .profiles
+                .values()
+                .flat_map(|profile| profile.runs.iter().chain(profile.templates.values())),
+        )
+        .flat_map(|run| &run.command_tags)
+        .chain(config.tag_options.keys())
+        .collect();
+    for tag in tags {
+        println!("{tag}");
+    }
+    Ok(())
+}
+
+/// Prints the names of the runs of the given config file (or the default one), one per line, the
+/// runs of the profiles included. Errors are ignored, as for the tags.
+pub async fn print_names(file: Option<PathBuf>) -> anyhow::Result<()> {
+    let file = file.unwrap_or_else(|| Config::default_path("."));
+    let Ok((config, _)) = Config::load(file).await else {
+        return Ok(());
+    };
+
+    let mut names = BTreeSet::new();
+    let mut insert = |run: &Command, templates: &BTreeMap<String, Command>| {
+        // the runs without a name are named after their program, which may be inherited
+        if let Ok(run) = run.clone().extend_templates(templates) {
+            names.extend(run.command_name.or(run.command_cmd.into_iter().next()));
+        }
+    };
+    for run in &config.runs {
+        insert(run, &config.templates);
+    }
+    for profile in config.profiles.values() {
+        let mut templates = config.templates.clone();
+        templates.extend(profile.templates.clone());
+        for run in &profile.runs {
+            insert(run, &templates);
+        }
+    }
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+const BASH_DYNAMIC: &str = r#"
+_run_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}" i
+    local -a file=()
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -f|--file) file=(--file "${COMP_WORDS[i+1]}") ;;
+            --file=*) file=("${COMP_WORDS[i]}") ;;
+            SUBCOMMANDS) _run "$@"; return 0 ;;
+        esac
+    done
+
+    case "$prev" in
+        -t|--tags|--exclude-tags)
+            COMPREPLY=($(compgen -W "$(run "${file[@]}" complete-tags 2>/dev/null)" -- "$cur"))
+            return 0
+            ;;
+        -n|--name)
+            COMPREPLY=($(compgen -W "$(run "${file[@]}" complete-names 2>/dev/null)" -- "$cur"))
+            return 0
+            ;;
+    esac
+    _run "$@"
+}
+
+complete -F _run_dynamic -o bashdefault -o default run
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_run_file_args() {
+    local i
+    file_args=()
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${words[i]}" in
+            -f|--file) file_args=(--file "${words[i+1]}") ;;
+            --file=*) file_args=("${words[i]}") ;;
+        esac
+    done
+}
+
+_run_tags() {
+    local -a tags file_args
+    _run_file_args
+    tags=(${(f)"$(run $file_args complete-tags 2>/dev/null)"})
+    _describe 'tag' tags
+}
+
+_run_names() {
+    local -a names file_args
+    _run_file_args
+    names=(${(f)"$(run $file_args complete-names 2>/dev/null)"})
+    _wanted names expl 'name' compadd -a names
+}
+"#;
+
+const FISH_DYNAMIC: &str = "
+function __run_file_args
+    set -l tokens (commandline -opc)
+    set -l file
+    for i in (seq 2 (count $tokens))
+        switch $tokens[$i]
+            case -f --file
+                set file --file $tokens[(math $i + 1)]
+            case '--file=*'
+                set file $tokens[$i]
+        end
+    end
+    printf '%s\\n' $file
+end
+
+complete -c run -s t -l tags -x -a '(run (__run_file_args) complete-tags 2>/dev/null)'
+complete -c run -l exclude-tags -x -a '(run (__run_file_args) complete-tags 2>/dev/null)'
+complete -c run -n __fish_use_subcommand -s n -l name -x -a '(run (__run_file_args) complete-names 2>/dev/null)'
+";
@@ -1,19 +1,25 @@
+use crate::diagnostic;
+use crate::loaders::Loader;
 use crate::runner::{
     RunnerCommand, RunnerLog, RunnerMode, RunnerOpenai, RunnerOptions, RunnerPrefix, RunnerTmux,
 };
+use crate::tags::TagFilter;
 use anyhow::bail;
 use anyhow::Context;
 use clap::Parser;
 use clap::ValueEnum;
 use merge::Merge;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
 /*
  * Shared configuration for the command line interface and the TOML configuration file.
  */
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Parser, Merge)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Parser, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Config {
     #[arg(
@@ -26,6 +32,16 @@ pub struct Config {
     #[merge(strategy = merge::vec::prepend)] // highest priority is at the end
     pub envs: Vec<String>,
 
+    #[arg(
+        long = "exclude-tags",
+        env = "RUN_CLI_EXCLUDE_TAGS",
+        help = "Filter out the commands matching any of the given tags. Can be comma-separated or passed multiple times",
+        value_name = "TAG[,TAG]...",
+        use_value_delimiter = true
+    )]
+    #[serde(rename = "exclude_tags")]
+    pub exclude_tags: Option<Vec<String>>,
+
     #[command(flatten)]
     #[serde(rename = "log")]
     pub log: Log,
@@ -48,6 +64,22 @@ pub struct Config {
     #[serde(rename = "prefix")]
     pub prefix: Prefix,
 
+    #[arg(
+        long,
+        env = "RUN_CLI_PROFILE",
+        help = "Activate a profile from the config file, overriding its top-level options"
+    )]
+    #[serde(skip)]
+    pub profile: Option<String>,
+
+    #[arg(skip)]
+    #[serde(rename = "profile")]
+    #[schemars(
+        description = "Profiles overriding the top-level options, activated with --profile"
+    )]
+    #[merge(strategy = merge_profiles)]
+    pub profiles: BTreeMap<String, Config>,
+
     #[arg(
         short,
         long,
@@ -66,16 +98,28 @@ pub struct Config {
     #[merge(strategy = merge::vec::append)]
     pub runs: Vec<Command>,
 
+    #[arg(skip)]
+    #[serde(rename = "tag")]
+    #[schemars(description = "Options applied to all the commands carrying a tag")]
+    #[merge(strategy = merge_tags)]
+    pub tag_options: BTreeMap<String, Tag>,
+
     #[arg(
         short,
         long = "tags",
         env = "RUN_CLI_TAGS",
-        help = "Filter to only run the commands matching at least one of the given tags. Can be comma-separated or passed multiple times",
+        help = "Filter to only run the commands matching at least one of the given tags. Can be comma-separated or passed multiple times. Supports `&` (and), `!` (not) and parentheses",
         value_name = "TAG[,TAG]...",
         use_value_delimiter = true
     )]
     pub tags: Option<Vec<String>>,
 
+    #[arg(skip)]
+    #[serde(rename = "template")]
+    #[schemars(description = "Templates of runs, inherited by the runs extending them")]
+    #[merge(strategy = merge_templates)]
+    pub templates: BTreeMap<String, Command>,
+
     #[command(flatten)]
     #[serde(rename = "tmux")]
     pub tmux: Tmux,
@@ -89,29 +133,143 @@ pub struct Config {
     pub workdir: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Command {
+    /// The program to run, followed by its arguments
     #[serde(rename = "cmd")]
+    #[merge(strategy = replace_empty)]
     pub command_cmd: Vec<String>,
 
+    /// Arguments appended to the command, e.g. to the `cmd` inherited from a template
+    #[serde(rename = "args")]
+    #[merge(strategy = replace_empty)]
+    pub command_args: Vec<String>,
+
+    /// Environment variables to set for this command, as KEY=VALUE
     #[serde(rename = "env")]
+    #[merge(strategy = merge::vec::prepend)] // highest priority is at the end
     pub command_envs: Vec<String>,
 
+    /// Directories to prepend to the PATH of this command, relative to its working directory
+    #[serde(rename = "path")]
+    #[merge(strategy = merge::vec::append)] // highest priority is at the start
+    pub command_path: Vec<PathBuf>,
+
+    /// The name of the command, used as a prefix (defaults to the program)
     #[serde(rename = "name")]
     pub command_name: Option<String>,
 
+    /// A description of what the command does
     #[serde(rename = "description")]
     pub command_description: Option<String>,
 
+    /// Tags used to filter the commands to run
     #[serde(rename = "tags")]
+    #[merge(strategy = replace_empty)]
     pub command_tags: Vec<String>,
 
+    /// The template to inherit the command and the options from
+    #[serde(rename = "extends")]
+    pub command_extends: Option<String>,
+
+    /// The working directory of the command, relative to the base working directory
     #[serde(rename = "workdir")]
     pub command_workdir: Option<PathBuf>,
+
+    /// Override the log options for this command
+    #[serde(rename = "log")]
+    pub command_log: Log,
+
+    /// Override the `OpenAI` options for this command
+    #[serde(rename = "openai")]
+    pub command_openai: Openai,
+
+    /// Override the prefix options for this command
+    #[serde(rename = "prefix")]
+    pub command_prefix: Prefix,
+
+    /// Override the raw option for this command
+    #[serde(rename = "raw")]
+    pub command_raw: Option<Option<bool>>,
+
+    #[serde(skip)]
+    pub command_source: Option<Source>,
+}
+
+/// Options shared by all the commands carrying a tag. They take precedence over the top-level
+/// options, but not over the options of the run itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Merge)]
+#[serde(deny_unknown_fields, default)]
+pub struct Tag {
+    /// Environment variables to set for the commands carrying this tag, as KEY=VALUE
+    #[serde(rename = "env")]
+    #[merge(strategy = merge::vec::prepend)] // highest priority is at the end
+    pub tag_envs: Vec<String>,
+
+    /// Override the log options for the commands carrying this tag
+    #[serde(rename = "log")]
+    pub tag_log: Log,
+
+    /// Override the `OpenAI` options for the commands carrying this tag
+    #[serde(rename = "openai")]
+    pub tag_openai: Openai,
+
+    /// Override the prefix options for the commands carrying this tag
+    #[serde(rename = "prefix")]
+    pub tag_prefix: Prefix,
+
+    /// Override the raw option for the commands carrying this tag
+    #[serde(rename = "raw")]
+    pub tag_raw: Option<Option<bool>>,
+
+    /// The working directory of the commands carrying this tag, relative to the base working
+    /// directory
+    #[serde(rename = "workdir")]
+    pub tag_workdir: Option<PathBuf>,
+}
+
+/// The location of a run in the config file it has been loaded from.
+#[derive(Debug, Clone)]
+pub struct Source {
+    pub path: PathBuf,
+    pub span: Range<usize>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Parser, Merge)]
+impl Source {
+    /// Points an error raised while resolving a run to its location in the config file.
+    pub fn locate(&self, err: anyhow::Error) -> anyhow::Error {
+        anyhow::Error::new(RunError {
+            source: self.clone(),
+            error: err,
+        })
+    }
+}
+
+/// An error raised while resolving a run, rendered with the snippet of the run in its config file.
+#[derive(Debug)]
+pub struct RunError {
+    pub source: Source,
+    pub error: anyhow::Error,
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match std::fs::read_to_string(&self.source.path) {
+            Ok(original) => f.write_str(&diagnostic::render(
+                &format!("{:#}", self.error),
+                &self.source.path,
+                &original,
+                self.source.span.clone(),
+            )),
+            Err(_) => write!(f, "{:#}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Parser, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Log {
     #[arg(
@@ -151,7 +309,7 @@ pub struct Log {
     pub log_terminations: Option<Option<bool>>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Parser, Merge)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Parser, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Openai {
     #[arg(
@@ -183,7 +341,7 @@ pub struct Openai {
     pub openai_api_key: Option<String>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, ValueEnum)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
     #[default]
@@ -192,7 +350,7 @@ pub enum Mode {
     Tmux,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Parser, Merge)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Parser, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Prefix {
     #[arg(
@@ -208,7 +366,7 @@ pub struct Prefix {
     pub prefix_enabled: Option<Option<bool>>,
 }
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, Parser, Merge)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, Parser, Merge)]
 #[serde(deny_unknown_fields, default)]
 pub struct Tmux {
     #[arg(
@@ -249,7 +407,13 @@ pub struct Tmux {
 }
 
 impl Config {
-    pub async fn load<P: AsRef<Path>>(relpath: P) -> anyhow::Result<Config> {
+    /// The file names looked up, in order, when no config file is explicitly provided.
+    pub const DEFAULT_FILE_NAMES: [&'static str; 5] =
+        ["run.toml", "run.yaml", "run.yml", "run.json", "Procfile"];
+
+    /// Loads a config file, or the config file of another tool, with the warnings about what could
+    /// not be translated from the latter.
+    pub async fn load<P: AsRef<Path>>(relpath: P) -> anyhow::Result<(Config, Vec<String>)> {
         let config_path = Self::resolve_absolute_config_path(&relpath).with_context(|| {
             format!(
                 "failed to resolve the absolute config file path from input {}",
@@ -257,31 +421,41 @@ impl Config {
             )
         })?;
 
-        // We have to do this to check the complete extension (e.g. ".toml.md")
-        let config_path_str = config_path.to_string_lossy();
-
-        let mut config = if config_path_str.ends_with(".toml") {
-            Self::load_config_toml(&config_path)
-                .await
-                .with_context(|| {
-                    format!(
-                        "failed to load the config file at {}",
-                        config_path.display()
-                    )
-                })?
-        } else if config_path_str.ends_with(".toml.md") {
-            Self::load_config_toml_literate(&config_path)
-                .await
-                .with_context(|| {
-                    format!(
-                        "failed to load the literate config file at {}",
-                        config_path.display()
-                    )
-                })?
+        let (mut config, warnings) = if let Some(loader) = Loader::detect(&config_path) {
+            loader.load(&config_path).await.with_context(|| {
+                format!(
+                    "failed to load the {} at {}",
+                    loader.name(),
+                    config_path.display()
+                )
+            })?
         } else {
-            bail!("unsupported config file format");
+            let config = match ConfigFormat::detect(&config_path) {
+                Some((format, false)) => Self::load_config(&config_path, format)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to load the config file at {}",
+                            config_path.display()
+                        )
+                    })?,
+                Some((format, true)) => Self::load_config_literate(&config_path, format)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "failed to load the literate config file at {}",
+                            config_path.display()
+                        )
+                    })?,
+                None => bail!("unsupported config file format"),
+            };
+            (config, vec![])
         };
 
+        if config.profiles.values().any(|p| !p.profiles.is_empty()) {
+            bail!("profiles cannot be nested in {}", config_path.display());
+        }
+
         config.set_absolute_workdir(&config_path).with_context(|| {
             format!(
                 "failed to set absolute workdir path from config file {}",
@@ -289,62 +463,362 @@ impl Config {
             )
         })?;
 
-        Ok(config)
+        Ok((config, warnings))
+    }
+
+    /// Loads the user config, with the personal preferences of the user shared by all the
+    /// projects (e.g. `tmux.program` or `openai.api_key`). It is looked up at
+    /// `$XDG_CONFIG_HOME/run/config.toml`, or `~/.config/run/config.toml`, and is ignored when it
+    /// does not exist. It cannot define runs nor a working directory, which belong to the projects.
+    pub async fn load_user() -> anyhow::Result<Option<(PathBuf, Config)>> {
+        let Some(config_path) = Self::user_config_path() else {
+            return Ok(None);
+        };
+        if !config_path.is_file() {
+            return Ok(None);
+        }
+
+        let context = || {
+            format!(
+                "failed to load the user config at {}",
+                config_path.display()
+            )
+        };
+        let config = Self::load_config(&config_path, ConfigFormat::Toml)
+            .await
+            .with_context(context)?;
+        if !config.runs.is_empty() || config.profiles.values().any(|p| !p.runs.is_empty()) {
+            return Err(anyhow::anyhow!(
+                "the runs cannot be defined in the user config"
+            ))
+            .with_context(context);
+        }
+        if config.workdir.is_some() || config.profiles.values().any(|p| p.workdir.is_some()) {
+            return Err(anyhow::anyhow!("workdir cannot be set in the user config"))
+                .with_context(context);
+        }
+        if config.profiles.values().any(|p| !p.profiles.is_empty()) {
+            return Err(anyhow::anyhow!("profiles cannot be nested")).with_context(context);
+        }
+        Ok(Some((config_path, config)))
+    }
+
+    fn user_config_path() -> Option<PathBuf> {
+        // relative paths are invalid as per the XDG Base Directory specification
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("run").join("config.toml"))
     }
 
     pub fn merge(&mut self, other: Self) {
         Merge::merge(self, other);
     }
 
+    /// Merges the options of `layer` which can be set per run (log, prefix, openai and raw) into
+    /// all the runs, so that they take precedence over the options of the runs and of their tags.
+    pub fn merge_into_runs(&mut self, layer: &Self) {
+        for run in &mut self.runs {
+            let mut log = layer.log.clone();
+            log.merge(std::mem::take(&mut run.command_log));
+            run.command_log = log;
+
+            let mut openai = layer.openai.clone();
+            openai.merge(std::mem::take(&mut run.command_openai));
+            run.command_openai = openai;
+
+            let mut prefix = layer.prefix.clone();
+            prefix.merge(std::mem::take(&mut run.command_prefix));
+            run.command_prefix = prefix;
+
+            run.command_raw = layer.raw.or(run.command_raw);
+        }
+    }
+
+    /// Merges the profile activated in `self` (if any) and then `other`. The profile has a lower
+    /// priority than `self`, but a higher priority than the rest of `other`. The runs of the
+    /// profile are appended after the runs of `other`.
+    pub fn merge_with_profile(&mut self, mut other: Self) -> anyhow::Result<()> {
+        let mut runs = vec![];
+
+        if let Some(name) = &self.profile {
+            let mut profile = other.profiles.remove(name).ok_or_else(|| {
+                anyhow::anyhow!("profile `{}` not found in the config file", name)
+            })?;
+            runs = std::mem::take(&mut profile.runs);
+            self.merge(profile);
+        }
+
+        self.merge(other);
+        self.runs.extend(runs);
+        Ok(())
+    }
+
+    /// Returns the first default config file found in `dir`, falling back to `run.toml`.
+    pub fn default_path<P: AsRef<Path>>(dir: P) -> PathBuf {
+        let dir = dir.as_ref();
+        Self::DEFAULT_FILE_NAMES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+            .unwrap_or_else(|| dir.join(Self::DEFAULT_FILE_NAMES[0]))
+    }
+
     fn resolve_absolute_config_path<P: AsRef<Path>>(relpath: P) -> anyhow::Result<PathBuf> {
         let mut config_path = std::env::current_dir()?;
         config_path.push(relpath);
         if std::fs::metadata(&config_path)?.is_dir() {
-            config_path.push("run.toml");
+            config_path = Self::default_path(&config_path);
         }
         Ok(config_path.canonicalize()?)
     }
 
-    async fn load_config_toml<P: AsRef<Path>>(abspath: P) -> anyhow::Result<Config> {
-        let config_str = tokio::fs::read_to_string(&abspath).await?;
-        Ok(toml::from_str(&config_str)?)
+    async fn load_config<P: AsRef<Path>>(
+        abspath: P,
+        format: ConfigFormat,
+    ) -> anyhow::Result<Config> {
+        let abspath = abspath.as_ref();
+        let config_str = tokio::fs::read_to_string(abspath).await?;
+        Self::parse_config(abspath, &config_str, format).map_err(|err| err.render(abspath))
     }
 
-    async fn load_config_toml_literate<P: AsRef<Path>>(abspath: P) -> anyhow::Result<Config> {
-        use pulldown_cmark::{CodeBlockKind, Event, Tag};
+    async fn load_config_literate<P: AsRef<Path>>(
+        abspath: P,
+        format: ConfigFormat,
+    ) -> anyhow::Result<Config> {
+        let abspath = abspath.as_ref();
+        let config_str = tokio::fs::read_to_string(abspath).await?;
+        Self::parse_config_literate(abspath, &config_str, format).map_err(|err| err.render(abspath))
+    }
+
+    /// Parses the content of a config file (e.g. the unsaved content of an editor), without
+    /// resolving its working directories. The location of the error is relative to `config_str`.
+    pub fn parse_str(path: &Path, config_str: &str) -> Result<Config, ParseError> {
+        match ConfigFormat::detect(path) {
+            Some((format, false)) => Self::parse_config(path, config_str, format),
+            Some((format, true)) => Self::parse_config_literate(path, config_str, format),
+            None => Err(ParseError {
+                message: "unsupported config file format".into(),
+                span: None,
+            }),
+        }
+    }
+
+    fn parse_config(
+        abspath: &Path,
+        config_str: &str,
+        format: ConfigFormat,
+    ) -> Result<Config, ParseError> {
+        let source_map = SourceMap::verbatim();
+        let mut config = format
+            .parse(config_str)
+            .map_err(|err| err.map(&source_map))?;
 
-        let config_str = tokio::fs::read_to_string(&abspath).await?;
-        let parser = pulldown_cmark::Parser::new(&config_str);
+        let spans = format.run_spans(config_str);
+        set_run_sources(&mut config, abspath, spans, &source_map);
 
+        Ok(config)
+    }
+
+    fn parse_config_literate(
+        abspath: &Path,
+        config_str: &str,
+        format: ConfigFormat,
+    ) -> Result<Config, ParseError> {
+        let blocks = Self::parse_literate_blocks(config_str, format)?;
+
+        // TOML blocks are concatenated and parsed as a single document, so that tables can span
+        // several blocks.
+        if let ConfigFormat::Toml = format {
+            let (toml_str, toml_source_map) = Self::concat_literate_toml(blocks)?;
+            let mut config = format
+                .parse(&toml_str)
+                .map_err(|err| err.map(&toml_source_map))?;
+            let spans = format.run_spans(&toml_str);
+            set_run_sources(&mut config, abspath, spans, &toml_source_map);
+            return Ok(config);
+        }
+
+        // YAML and JSON blocks are self-contained documents, so they are parsed one by one and
+        // merged in order.
+        let mut config = Config::default();
+        for mut block in blocks {
+            if let Some(mut run) = block.run.take() {
+                run.command_cmd.push(block.source);
+                run.command_source = Some(Source {
+                    path: abspath.to_owned(),
+                    span: block.span,
+                });
+                config.runs.push(run);
+            } else {
+                let mut block_config = format
+                    .parse(&block.source)
+                    .map_err(|err| err.map(&block.source_map))?;
+                let spans = format.run_spans(&block.source);
+                set_run_sources(&mut block_config, abspath, spans, &block.source_map);
+                config.merge(block_config);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Concatenates the TOML code blocks of a literate config, the runnable code blocks being
+    /// translated to `[[run]]` tables, with the map of the offsets back to the literate config.
+    fn concat_literate_toml(blocks: Vec<LiterateBlock>) -> Result<(String, SourceMap), ParseError> {
         let mut toml_str = String::new();
-        let mut in_code_block = false;
-        for event in parser {
+        let mut toml_source_map = SourceMap::default();
+        for block in blocks {
+            if let Some(mut run) = block.run {
+                run.command_cmd.push(block.source);
+                let runs = std::collections::BTreeMap::from([("run", [run])]);
+                toml_source_map.push(toml_str.len(), block.span.start, false);
+                toml_str.push('\n');
+                toml_str.push_str(&toml::to_string(&runs).map_err(|err| ParseError {
+                    message: err.to_string(),
+                    span: Some(block.span.clone()),
+                })?);
+            } else {
+                toml_source_map.extend(toml_str.len(), block.source_map);
+                toml_str.push_str(&block.source);
+            }
+        }
+        Ok((toml_str, toml_source_map))
+    }
+
+    /// Returns the TOML document of a TOML config file, literate or not, with the map of its
+    /// offsets back to `config_str`. Used to locate the keys of the config.
+    pub fn toml_document(path: &Path, config_str: &str) -> Option<(String, SourceMap)> {
+        match ConfigFormat::detect(path)? {
+            (ConfigFormat::Toml, false) => Some((config_str.to_owned(), SourceMap::verbatim())),
+            (ConfigFormat::Toml, true) => {
+                let blocks = Self::parse_literate_blocks(config_str, ConfigFormat::Toml).ok()?;
+                Self::concat_literate_toml(blocks).ok()
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_literate_blocks(
+        config_str: &str,
+        format: ConfigFormat,
+    ) -> Result<Vec<LiterateBlock>, ParseError> {
+        use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+        let parser = pulldown_cmark::Parser::new(config_str).into_offset_iter();
+
+        let mut blocks: Vec<LiterateBlock> = vec![];
+        let mut code_block: Option<LiterateBlock> = None;
+        let mut heading: Option<String> = None;
+        let mut paragraph: Option<String> = None;
+        let mut text: Option<String> = None;
+        for (event, span) in parser {
             match event {
-                Event::Start(Tag::CodeBlock(code_block)) => match code_block {
-                    CodeBlockKind::Fenced(code) if code == "toml".into() => {
-                        in_code_block = true;
-                    }
-                    _ => bail!("code block must start with ```toml (indented is not allowed)"),
-                },
-                Event::Text(text) if in_code_block => {
-                    toml_str.push_str(&text);
+                Event::Start(Tag::CodeBlock(kind)) => {
+                    let info = match kind {
+                        CodeBlockKind::Fenced(info) if !info.is_empty() => info,
+                        _ => {
+                            return Err(ParseError {
+                                message: format!(
+                                    "code block must start with ```{} (indented is not allowed)",
+                                    format.language()
+                                ),
+                                span: Some(span),
+                            })
+                        }
+                    };
+                    let description = paragraph.take();
+                    let run = if format.matches_language(&info) {
+                        None
+                    } else if let Some(run) =
+                        parse_runnable_code_block(&info).map_err(|err| ParseError {
+                            message: err.to_string(),
+                            span: Some(span.clone()),
+                        })?
+                    {
+                        Some(Command {
+                            command_name: run.command_name.or_else(|| heading.clone()),
+                            command_description: description,
+                            ..run
+                        })
+                    } else if format.is_misspelled(&info) {
+                        return Err(ParseError {
+                            message: format!(
+                                "code block ```{info} is neither config nor runnable, did you mean ```{}?",
+                                format.language()
+                            ),
+                            span: Some(span),
+                        });
+                    } else {
+                        continue; // code blocks in other languages are documentation
+                    };
+                    code_block = Some(LiterateBlock {
+                        source: String::new(),
+                        source_map: SourceMap::default(),
+                        span,
+                        run,
+                    });
                 }
                 Event::End(Tag::CodeBlock(_)) => {
-                    in_code_block = false;
+                    if let Some(block) = code_block.take() {
+                        blocks.push(block);
+                    }
+                }
+                Event::Text(t) if code_block.is_some() => {
+                    if let Some(block) = code_block.as_mut() {
+                        block.source_map.push(block.source.len(), span.start, true);
+                        block.source.push_str(&t);
+                    }
+                }
+                Event::Start(Tag::Heading(..) | Tag::Paragraph) => {
+                    text = Some(String::new());
+                }
+                Event::End(Tag::Heading(..)) => {
+                    heading = text.take();
+                    paragraph = None;
+                }
+                Event::End(Tag::Paragraph) => {
+                    paragraph = text.take();
+                }
+                Event::Text(t) | Event::Code(t) => {
+                    if let Some(text) = text.as_mut() {
+                        text.push_str(&t);
+                    }
+                }
+                Event::SoftBreak | Event::HardBreak => {
+                    if let Some(text) = text.as_mut() {
+                        text.push(' ');
+                    }
                 }
                 _ => {}
             }
         }
 
-        Ok(toml::from_str(&toml_str)?)
+        Ok(blocks)
     }
 
-    fn set_absolute_workdir<P: AsRef<Path>>(&mut self, config_path: P) -> anyhow::Result<()> {
+    /// The base working directory of the commands, which defaults to the current directory. The
+    /// workdirs of the config files are already absolute, the ones passed on the command line
+    /// (`--workdir` or `--set workdir=...`) are relative to the current directory.
+    pub fn resolve_workdir(&self) -> anyhow::Result<PathBuf> {
+        let cwd = std::env::current_dir().context("failed to get the current directory")?;
+        Ok(match &self.workdir {
+            Some(workdir) => cwd.join(workdir),
+            None => cwd,
+        })
+    }
+
+    pub fn set_absolute_workdir<P: AsRef<Path>>(&mut self, config_path: P) -> anyhow::Result<()> {
         let mut workdir = config_path
             .as_ref()
             .parent()
             .ok_or_else(|| anyhow::anyhow!("config file has no parent directory"))?
             .to_owned();
+        for profile in self.profiles.values_mut() {
+            if let Some(w) = profile.workdir.as_ref() {
+                profile.workdir = Some(workdir.join(w).canonicalize()?);
+            }
+        }
         if let Some(w) = self.workdir.as_ref() {
             workdir.push(w); // use provided workdir if found
         }
@@ -353,78 +827,526 @@ impl Config {
     }
 }
 
-impl TryFrom<Config> for RunnerOptions {
-    type Error = anyhow::Error;
+#[derive(Debug, Clone, Copy)]
+enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the complete file extension (e.g. ".toml.md"), and whether the
+    /// file is a literate config.
+    fn detect<P: AsRef<Path>>(path: P) -> Option<(Self, bool)> {
+        let path_str = path.as_ref().to_string_lossy();
+        let (path_str, literate) = match path_str.strip_suffix(".md") {
+            Some(stripped) => (stripped, true),
+            None => (path_str.as_ref(), false),
+        };
 
-    fn try_from(config: Config) -> Result<Self, Self::Error> {
-        let raw = resolve_bool(config.raw, false);
+        let format = match Path::new(path_str).extension()?.to_str()? {
+            "json" => Self::Json,
+            "toml" => Self::Toml,
+            "yaml" | "yml" => Self::Yaml,
+            _ => return None,
+        };
 
-        let workdir = config
-            .workdir
-            .unwrap_or(std::env::current_dir().expect("infaillible"));
-        if !workdir.is_absolute() {
-            anyhow::bail!("workdir must be an absolute path");
+        Some((format, literate))
+    }
+
+    fn language(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
         }
+    }
 
-        if config.runs.is_empty() {
-            anyhow::bail!("no commands found in the config file or CLI arguments");
+    fn matches_language(self, lang: &str) -> bool {
+        lang == self.language() || matches!((self, lang), (Self::Yaml, "yml"))
+    }
+
+    /// Whether the language of a code block is a typo of the language of the format (e.g. `tml`
+    /// or `TOML`), which would otherwise be taken for documentation.
+    fn is_misspelled(self, info: &str) -> bool {
+        let lang = info.split_whitespace().next().unwrap_or_default();
+        !self.matches_language(lang)
+            && strsim::levenshtein(&lang.to_ascii_lowercase(), self.language()) <= 1
+    }
+
+    fn parse(self, config_str: &str) -> Result<Config, ParseError> {
+        match self {
+            Self::Json => serde_json::from_str(config_str).map_err(|err| {
+                let suffix = format!(" at line {} column {}", err.line(), err.column());
+                let span = offset_of(config_str, err.line(), err.column()).map(|o| o..o + 1);
+                ParseError::strip(&err, &suffix, span)
+            }),
+            Self::Toml => toml::from_str(config_str).map_err(|err| ParseError {
+                message: err.message().trim_end().to_owned(),
+                span: err.span(),
+            }),
+            Self::Yaml => serde_norway::from_str(config_str).map_err(|err| match err.location() {
+                Some(location) => {
+                    let suffix =
+                        format!(" at line {} column {}", location.line(), location.column());
+                    let span = Some(location.index()..location.index() + 1);
+                    ParseError::strip(&err, &suffix, span)
+                }
+                None => ParseError {
+                    message: err.to_string(),
+                    span: None,
+                },
+            }),
         }
-        let commands = config
-            .runs
-            .into_iter()
-            .map(|run| {
-                let program = match run.command_cmd.get(0) {
-                    Some(p) => p.to_string(),
-                    _ => anyhow::bail!("no program found"),
-                };
-
-                let args = match run.command_cmd.get(1..) {
-                    Some(a) => a.to_vec(),
-                    _ => anyhow::bail!("no args found"),
-                };
-
-                let description = run.command_description;
-
-                let envs: Vec<_> = config
-                    .envs
-                    .iter()
-                    .chain(run.command_envs.iter())
-                    .map(|kv| match kv.split_once('=') {
-                        Some((k, v)) => Ok((k.to_string(), v.to_string())),
-                        _ => anyhow::bail!("invalid environment variable: {}", kv),
-                    })
-                    .collect::<anyhow::Result<Vec<_>>>()?;
+    }
+
+    /// Locates each run of a config, in order: the `[[run]]` headers in TOML, and the first line
+    /// of the items of the `run` sequence in YAML and JSON.
+    fn run_spans(self, config_str: &str) -> Vec<Range<usize>> {
+        match self {
+            Self::Toml => toml_run_spans(config_str),
+            // JSON is read as YAML in flow style
+            Self::Json | Self::Yaml => yaml_run_spans(config_str),
+        }
+    }
+}
 
-                let name = run.command_name.unwrap_or(program.clone());
+/// A code block of a literate config file. `run` is set for runnable code blocks, otherwise the
+/// block contains config.
+struct LiterateBlock {
+    source: String,
+    source_map: SourceMap,
+    span: Range<usize>,
+    run: Option<Command>,
+}
 
-                let tags = run.command_tags;
+/// Maps the offsets of a generated config (e.g. the concatenation of the code blocks of a
+/// literate config) back to the offsets of the original file. Each segment starts at a generated
+/// offset, and is either a verbatim copy of the original file or generated from a single location.
+#[derive(Default)]
+pub struct SourceMap {
+    segments: Vec<(usize, usize, bool)>,
+}
 
-                let workdir = run
-                    .command_workdir
-                    .map(|w| {
-                        let mut abs = workdir.clone();
-                        abs.push(w);
-                        abs.canonicalize().expect("infaillible")
-                    })
-                    .unwrap_or(workdir.clone());
-
-                Ok(RunnerCommand {
-                    program,
-                    args,
-                    description,
-                    envs,
-                    name,
-                    tags,
-                    workdir,
-                })
+impl SourceMap {
+    fn verbatim() -> Self {
+        Self {
+            segments: vec![(0, 0, true)],
+        }
+    }
+
+    fn push(&mut self, generated: usize, original: usize, verbatim: bool) {
+        self.segments.push((generated, original, verbatim));
+    }
+
+    fn extend(&mut self, generated: usize, other: SourceMap) {
+        for (g, original, verbatim) in other.segments {
+            self.push(generated + g, original, verbatim);
+        }
+    }
+
+    pub fn map(&self, offset: usize) -> usize {
+        match self.segments.iter().rev().find(|(g, _, _)| *g <= offset) {
+            Some((g, original, true)) => original + (offset - g),
+            Some((_, original, false)) => *original,
+            None => offset,
+        }
+    }
+
+    fn map_span(&self, span: &Range<usize>) -> Range<usize> {
+        let start = self.map(span.start);
+        start..self.map(span.end).max(start)
+    }
+}
+
+/// An error raised while parsing a config, with its location in the parsed string if known.
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Option<Range<usize>>,
+}
+
+impl ParseError {
+    /// `serde_json` and `serde_norway` append the location to the message, it is rendered separately.
+    fn strip<E: std::fmt::Display>(err: &E, suffix: &str, span: Option<Range<usize>>) -> Self {
+        let message = err.to_string();
+        Self {
+            message: message.strip_suffix(suffix).unwrap_or(&message).to_owned(),
+            span,
+        }
+    }
+
+    /// Maps the location of the error from the parsed string to the original string.
+    fn map(self, source_map: &SourceMap) -> Self {
+        Self {
+            span: self.span.map(|span| source_map.map_span(&span)),
+            ..self
+        }
+    }
+
+    /// Locates the error in the file at `path`, so that `--check` can report its location. It is
+    /// rendered with the snippet of the file it points at.
+    fn render(self, path: &Path) -> anyhow::Error {
+        match self.span {
+            Some(span) => Source {
+                path: path.to_owned(),
+                span,
+            }
+            .locate(anyhow::anyhow!(self.message)),
+            None => anyhow::anyhow!(self.message),
+        }
+    }
+}
+
+/// Converts a 1-based line and column to an offset.
+fn offset_of(source: &str, line: usize, column: usize) -> Option<usize> {
+    let line_start = match line {
+        0 => return None,
+        1 => 0,
+        _ => source.match_indices('\n').nth(line - 2)?.0 + 1,
+    };
+    Some((line_start + column.saturating_sub(1)).min(source.len()))
+}
+
+/// Sets the location of each run of a config. The spans are in the order of the runs, so the ones
+/// found are kept should the others fail to be located, which are left without a location.
+fn set_run_sources(
+    config: &mut Config,
+    path: &Path,
+    spans: Vec<Range<usize>>,
+    source_map: &SourceMap,
+) {
+    for (run, span) in config.runs.iter_mut().zip(spans) {
+        run.command_source = Some(Source {
+            path: path.to_owned(),
+            span: source_map.map_span(&span),
+        });
+    }
+}
+
+fn toml_run_spans(toml_str: &str) -> Vec<Range<usize>> {
+    #[derive(Deserialize)]
+    struct Runs {
+        #[serde(default)]
+        run: Vec<toml::Spanned<toml::Value>>,
+    }
+
+    toml::from_str::<Runs>(toml_str).map_or_else(
+        |_| vec![],
+        |runs| runs.run.iter().map(toml::Spanned::span).collect(),
+    )
+}
+
+/// Locates the items of the `run` sequence with the events of a YAML parser, whatever their
+/// style, anchors and aliases included (e.g. `run: *runs`). The span of an item is the rest of
+/// its first line, from its `-` in block style.
+fn yaml_run_spans(yaml_str: &str) -> Vec<Range<usize>> {
+    use yaml_rust2::parser::Parser;
+    use yaml_rust2::Event;
+
+    /// A sequence whose items are located: the `run` one, or an anchored one which may be aliased
+    /// as the `run` one.
+    struct Sequence {
+        items_depth: usize,
+        anchor: usize,
+        is_run: bool,
+        spans: Vec<Range<usize>>,
+    }
+
+    // the markers of the parser are in chars
+    let offsets: Vec<usize> = yaml_str
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain([yaml_str.len()])
+        .collect();
+
+    let mut runs = vec![];
+    let mut anchored: BTreeMap<usize, Vec<Range<usize>>> = BTreeMap::new();
+    let mut sequences: Vec<Sequence> = vec![];
+    let mut parser = Parser::new_from_str(yaml_str);
+    // the nesting of the current node, the documents being at 0
+    let mut depth = 0;
+    // whether the next node of the top-level mapping is a key, and the last key read
+    let mut is_key = true;
+    let mut key = None;
+    // the start of a block mapping is marked after its first key, so the start of an item is only
+    // known with the next event
+    let mut item: Option<usize> = None;
+    while let Ok((event, marker)) = parser.next_token() {
+        let Some(&offset) = offsets.get(marker.index()) else {
+            break;
+        };
+        if let (Some(start), Some(sequence)) = (item.take(), sequences.last_mut()) {
+            sequence.spans.push(item_span(yaml_str, start.min(offset)));
+        }
+        let is_node = matches!(
+            event,
+            Event::Scalar(..)
+                | Event::Alias(_)
+                | Event::MappingStart(..)
+                | Event::SequenceStart(..)
+        );
+        if is_node && sequences.last().is_some_and(|seq| seq.items_depth == depth) {
+            item = Some(offset);
+        }
+        let is_run = is_node && depth == 1 && !is_key && key.as_deref() == Some("run");
+        if is_node && depth == 1 {
+            if let Event::Scalar(value, ..) = &event {
+                if is_key {
+                    key = Some(value.clone());
+                }
+            }
+            is_key = !is_key;
+        }
+        match event {
+            Event::Alias(anchor) if is_run => {
+                runs = anchored.get(&anchor).cloned().unwrap_or_default();
+            }
+            Event::SequenceStart(anchor, _) => {
+                depth += 1;
+                if is_run || anchor > 0 {
+                    sequences.push(Sequence {
+                        items_depth: depth,
+                        anchor,
+                        is_run,
+                        spans: vec![],
+                    });
+                }
+            }
+            Event::MappingStart(..) => depth += 1,
+            Event::SequenceEnd => {
+                let ended = sequences.last().is_some_and(|seq| seq.items_depth == depth);
+                if let Some(sequence) = sequences.pop_if(|_| ended) {
+                    if sequence.is_run {
+                        runs.clone_from(&sequence.spans);
+                    }
+                    if sequence.anchor > 0 {
+                        anchored.insert(sequence.anchor, sequence.spans);
+                    }
+                }
+                depth -= 1;
+            }
+            Event::MappingEnd => depth -= 1,
+            Event::StreamEnd => break,
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// The span of a sequence item starting at `offset`, up to the end of its line (its trailing
+/// comma excluded), and including its `-` in block style.
+fn item_span(source: &str, offset: usize) -> Range<usize> {
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let before = source[line_start..offset].trim_end();
+    let start = match before.strip_suffix('-') {
+        Some(indicator) => line_start + indicator.len(),
+        None => offset,
+    };
+    let line_end = source[offset..]
+        .find('\n')
+        .map_or(source.len(), |i| offset + i);
+    let line = source[start..line_end].trim_end();
+    start..start + line.strip_suffix(',').unwrap_or(line).len()
+}
+
+/// Parses the info string of a runnable code block (e.g. "sh run name=migrate tags=db,sql").
+/// Returns `None` when the code block is not runnable. The script itself has to be appended to
+/// the returned command.
+fn parse_runnable_code_block(info: &str) -> anyhow::Result<Option<Command>> {
+    let mut words = info.split_whitespace();
+
+    let (Some(shell @ ("sh" | "bash")), Some("run")) = (words.next(), words.next()) else {
+        return Ok(None);
+    };
+
+    let mut run = Command {
+        command_cmd: vec![shell.to_owned(), "-c".to_owned()],
+        ..Default::default()
+    };
+
+    for word in words {
+        match word.split_once('=') {
+            Some(("name", name)) => run.command_name = Some(name.to_owned()),
+            Some(("tags", tags)) => {
+                run.command_tags = tags.split(',').map(ToOwned::to_owned).collect();
+            }
+            Some(("workdir", workdir)) => run.command_workdir = Some(workdir.into()),
+            _ => bail!(
+                "invalid attribute `{}` in code block ```{} (expected one of `name`, `tags`, `workdir`)",
+                word,
+                info
+            ),
+        }
+    }
+
+    Ok(Some(run))
+}
+
+impl Command {
+    /// Merges the templates extended by the run, a template being able to extend another one. The
+    /// run takes precedence: its command replaces the one of the template, its variables are set
+    /// after the ones of the template, and its arguments and tags are appended to the ones of the
+    /// template. The name of a template is not inherited.
+    pub fn extend_templates(
+        mut self,
+        templates: &BTreeMap<String, Command>,
+    ) -> anyhow::Result<Self> {
+        let mut chain: Vec<String> = vec![];
+        while let Some(name) = self.command_extends.take() {
+            if chain.contains(&name) {
+                chain.push(name);
+                bail!(
+                    "the templates extend each other in a cycle: {}",
+                    chain.join(" -> ")
+                );
+            }
+            let Some(template) = templates.get(&name) else {
+                bail!("template `{name}` not found");
+            };
+            chain.push(name);
+
+            let mut template = template.clone();
+            template.command_name = None;
+            let mut args = std::mem::take(&mut template.command_args);
+            args.append(&mut self.command_args);
+            let mut tags = std::mem::take(&mut template.command_tags);
+            for tag in std::mem::take(&mut self.command_tags) {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            self.merge(template);
+            self.command_args = args;
+            self.command_tags = tags;
+        }
+        Ok(self)
+    }
+
+    /// Resolves the command, the options of the run taking precedence over the options of its
+    /// tags, which take precedence over the global ones. When several tags set the same option,
+    /// the last one listed on the run wins.
+    pub fn try_into_runner_command(
+        self,
+        config: &Config,
+        workdir: &Path,
+    ) -> anyhow::Result<RunnerCommand> {
+        if self.command_extends.is_some() {
+            return self
+                .extend_templates(&config.templates)?
+                .try_into_runner_command(config, workdir);
+        }
+
+        let program = match self.command_cmd.first() {
+            Some(p) => p.to_string(),
+            _ => anyhow::bail!("no program found"),
+        };
+
+        let args = match self.command_cmd.get(1..) {
+            Some(a) => [a, &self.command_args].concat(),
+            _ => anyhow::bail!("no args found"),
+        };
+
+        let description = self.command_description;
+
+        let mut tag = Tag::default();
+        for name in self.command_tags.iter().rev() {
+            if let Some(options) = config.tag_options.get(name) {
+                tag.merge(options.clone());
+            }
+        }
+
+        let envs: Vec<_> = config
+            .envs
+            .iter()
+            .chain(tag.tag_envs.iter())
+            .chain(self.command_envs.iter())
+            .map(|kv| match kv.split_once('=') {
+                Some((k, v)) => Ok((k.to_string(), v.to_string())),
+                _ => anyhow::bail!("invalid environment variable: {}", kv),
             })
             .collect::<anyhow::Result<Vec<_>>>()?;
 
-        let log_enabled = resolve_bool(config.log.log_enabled, true);
-        let log = RunnerLog {
-            spawns: log_enabled && resolve_bool(config.log.log_spawns, false),
-            terminations: log_enabled && resolve_bool(config.log.log_terminations, true),
+        let mut log = self.command_log;
+        log.merge(tag.tag_log);
+        log.merge(config.log.clone());
+
+        let name = self.command_name.unwrap_or(program.clone());
+
+        let raw = resolve_bool(self.command_raw.or(tag.tag_raw).or(config.raw), false);
+
+        let mut openai = self.command_openai;
+        openai.merge(tag.tag_openai);
+        openai.merge(config.openai.clone());
+
+        let mut prefix = self.command_prefix;
+        prefix.merge(tag.tag_prefix);
+        prefix.merge(config.prefix.clone());
+
+        let tags = self.command_tags;
+
+        let workdir = match self.command_workdir.or(tag.tag_workdir) {
+            Some(w) => {
+                let abs = workdir.join(w);
+                abs.canonicalize()
+                    .with_context(|| format!("workdir {} does not exist", abs.display()))?
+            }
+            None => workdir.to_owned(),
         };
+        let path = self
+            .command_path
+            .into_iter()
+            .map(|dir| workdir.join(dir))
+            .collect();
+
+        Ok(RunnerCommand {
+            program,
+            args,
+            description,
+            envs,
+            log: resolve_log(&log),
+            name,
+            openai: resolve_openai(raw, openai),
+            path,
+            prefix: resolve_prefix(raw, &prefix),
+            source: self.command_source,
+            tags,
+            workdir,
+        })
+    }
+}
+
+impl TryFrom<Config> for RunnerOptions {
+    type Error = anyhow::Error;
+
+    fn try_from(mut config: Config) -> Result<Self, Self::Error> {
+        let workdir = config.resolve_workdir()?;
+
+        if config.runs.is_empty() {
+            anyhow::bail!("no commands found in the config file or CLI arguments");
+        }
+        // the global and tag environment variables are validated first, so that their errors are
+        // not attributed to the first run carrying them
+        if let Some(kv) = config
+            .envs
+            .iter()
+            .chain(config.tag_options.values().flat_map(|tag| &tag.tag_envs))
+            .find(|kv| !kv.contains('='))
+        {
+            anyhow::bail!("invalid environment variable: {}", kv);
+        }
+
+        let commands = std::mem::take(&mut config.runs)
+            .into_iter()
+            .map(|run| {
+                let source = run.command_source.clone();
+                run.try_into_runner_command(&config, &workdir)
+                    .map_err(|err| match &source {
+                        Some(source) => source.locate(err),
+                        None => err,
+                    })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
 
         let mode = match config.mode.unwrap_or(Mode::Sequential) {
             Mode::Sequential => RunnerMode::Sequential,
@@ -432,27 +1354,10 @@ impl TryFrom<Config> for RunnerOptions {
             Mode::Tmux => RunnerMode::Tmux,
         };
 
-        let openai = match (
-            raw,
-            resolve_bool(config.openai.openai_enabled, false),
-            config.openai.openai_api_key,
-        ) {
-            (false, true, Some(api_key)) => RunnerOpenai::Enabled {
-                api_key,
-                api_base_url: config
-                    .openai
-                    .openai_api_base_url
-                    .unwrap_or("https://api.openai.com".into()),
-            },
-            _ => RunnerOpenai::Disabled,
-        };
-
-        let prefix = match (raw, resolve_bool(config.prefix.prefix_enabled, true)) {
-            (false, true) => RunnerPrefix::Enabled,
-            _ => RunnerPrefix::Disabled,
-        };
-
-        let tags = config.tags.unwrap_or_default();
+        let tags = TagFilter::parse(
+            &config.tags.unwrap_or_default(),
+            &config.exclude_tags.unwrap_or_default(),
+        )?;
 
         let tmux = RunnerTmux {
             kill_duplicate_session: resolve_bool(config.tmux.tmux_kill_duplicate_session, true),
@@ -466,16 +1371,82 @@ impl TryFrom<Config> for RunnerOptions {
 
         Ok(Self {
             commands,
-            log,
             mode,
-            openai,
-            prefix,
+            names: vec![],
             tags,
             tmux,
+            workdir,
         })
     }
 }
 
+fn merge_profiles(left: &mut BTreeMap<String, Config>, right: BTreeMap<String, Config>) {
+    for (name, profile) in right {
+        left.entry(name).or_insert(profile);
+    }
+}
+
+/// Keeps the left list unless it is empty, for the lists which are replaced rather than merged
+/// (e.g. the program and arguments of a run).
+fn replace_empty<T>(left: &mut Vec<T>, right: Vec<T>) {
+    if left.is_empty() {
+        *left = right;
+    }
+}
+
+fn merge_templates(left: &mut BTreeMap<String, Command>, right: BTreeMap<String, Command>) {
+    for (name, template) in right {
+        match left.get_mut(&name) {
+            Some(existing) => existing.merge(template),
+            None => {
+                left.insert(name, template);
+            }
+        }
+    }
+}
+
+fn merge_tags(left: &mut BTreeMap<String, Tag>, right: BTreeMap<String, Tag>) {
+    for (name, tag) in right {
+        match left.get_mut(&name) {
+            Some(existing) => existing.merge(tag),
+            None => {
+                left.insert(name, tag);
+            }
+        }
+    }
+}
+
+fn resolve_log(log: &Log) -> RunnerLog {
+    let log_enabled = resolve_bool(log.log_enabled, true);
+    RunnerLog {
+        spawns: log_enabled && resolve_bool(log.log_spawns, false),
+        terminations: log_enabled && resolve_bool(log.log_terminations, true),
+    }
+}
+
+fn resolve_openai(raw: bool, openai: Openai) -> RunnerOpenai {
+    match (
+        raw,
+        resolve_bool(openai.openai_enabled, false),
+        openai.openai_api_key,
+    ) {
+        (false, true, Some(api_key)) => RunnerOpenai::Enabled {
+            api_key,
+            api_base_url: openai
+                .openai_api_base_url
+                .unwrap_or("https://api.openai.com".into()),
+        },
+        _ => RunnerOpenai::Disabled,
+    }
+}
+
+fn resolve_prefix(raw: bool, prefix: &Prefix) -> RunnerPrefix {
+    match (raw, resolve_bool(prefix.prefix_enabled, true)) {
+        (false, true) => RunnerPrefix::Enabled,
+        _ => RunnerPrefix::Disabled,
+    }
+}
+
 fn resolve_bool(opts: Option<Option<bool>>, default_value: bool) -> bool {
     match opts {
         Some(Some(b)) => b,
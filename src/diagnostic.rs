@@ -0,0 +1,57 @@
+use std::ops::Range;
+use std::path::Path;
+
+/// Renders a message pointing at `span` in `source`, with the file location and a snippet of the
+/// offending line, e.g.:
+///
+/// ```text
+/// invalid type: string "npm install", expected a sequence
+///  --> run.toml:2:7
+///   |
+/// 2 | cmd = "npm install"
+///   |       ^^^^^^^^^^^^^
+/// ```
+pub fn render(message: &str, path: &Path, source: &str, span: Range<usize>) -> String {
+    let start = floor_char_boundary(source, span.start);
+    let line_start = source
+        .get(..start)
+        .and_then(|s| s.rfind('\n'))
+        .map_or(0, |i| i + 1);
+    let line_end = source
+        .get(start..)
+        .and_then(|s| s.find('\n'))
+        .map_or(source.len(), |i| start + i);
+
+    let line_number = source.get(..start).map_or(0, |s| s.matches('\n').count()) + 1;
+    let line = source
+        .get(line_start..line_end)
+        .unwrap_or_default()
+        .trim_end_matches('\r');
+    let column = source
+        .get(line_start..start)
+        .map_or(0, |s| s.chars().count())
+        + 1;
+
+    // the caret is clipped to the offending line
+    let end = floor_char_boundary(source, span.end.clamp(start, line_end));
+    let width = source
+        .get(start..end)
+        .map_or(0, |s| s.chars().count())
+        .max(1);
+
+    let gutter = " ".repeat(line_number.to_string().len());
+    format!(
+        "{message}\n{gutter}--> {}:{line_number}:{column}\n{gutter} |\n{line_number} | {line}\n{gutter} | {}{}",
+        path.display(),
+        " ".repeat(column - 1),
+        "^".repeat(width),
+    )
+}
+
+fn floor_char_boundary(source: &str, index: usize) -> usize {
+    let mut index = index.min(source.len());
+    while !source.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
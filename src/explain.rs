@@ -0,0 +1,710 @@
+use crate::cli::Cli;
+use crate::config::{Command, Config, Log, Openai, Prefix, Source, SourceMap};
+use crate::overrides::Overrides;
+use crate::paths::relative_path;
+use crate::runner::{RunnerCommand, RunnerMode, RunnerOpenai, RunnerOptions, RunnerPrefix};
+use crate::table;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory};
+use serde::de::{Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+/// The configs the resolved options were merged from, kept apart to tell where each option comes
+/// from.
+pub struct Layers {
+    /// The options of the command line and of the `RUN_CLI_*` environment variables
+    pub cli: Config,
+    /// The config file as loaded, with its profiles
+    pub file: Option<(PathBuf, Config)>,
+    /// The user config, if any
+    pub user: Option<(PathBuf, Config)>,
+    /// The `--set` options
+    pub overrides: Overrides,
+    /// The runs, in the order of the resolved commands
+    pub runs: Vec<Command>,
+}
+
+/// The options set by a layer, whichever it is (the global options, a tag, a template or a run).
+#[derive(Clone, Copy)]
+struct Options<'a> {
+    /// Only set by the runs and the templates
+    cmd: &'a [String],
+    envs: &'a [String],
+    log: &'a Log,
+    openai: &'a Openai,
+    prefix: &'a Prefix,
+    raw: Option<Option<bool>>,
+    workdir: Option<&'a Path>,
+}
+
+impl<'a> From<&'a Config> for Options<'a> {
+    fn from(config: &'a Config) -> Self {
+        Self {
+            cmd: &[],
+            envs: &config.envs,
+            log: &config.log,
+            openai: &config.openai,
+            prefix: &config.prefix,
+            raw: config.raw,
+            workdir: config.workdir.as_deref(),
+        }
+    }
+}
+
+impl<'a> Options<'a> {
+    /// The options of a global layer which take precedence over the runs and their tags: the
+    /// variables and the base working directory are left out, as the runs build upon them.
+    fn over_runs(config: &'a Config) -> Self {
+        Self {
+            envs: &[],
+            workdir: None,
+            ..Self::from(config)
+        }
+    }
+}
+
+impl<'a> From<&'a Command> for Options<'a> {
+    fn from(run: &'a Command) -> Self {
+        Self {
+            cmd: &run.command_cmd,
+            envs: &run.command_envs,
+            log: &run.command_log,
+            openai: &run.command_openai,
+            prefix: &run.command_prefix,
+            raw: run.command_raw,
+            workdir: run.command_workdir.as_deref(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Origin<'a> {
+    /// A `--set` option, of the run with the given name if any
+    Set(Option<&'a str>),
+    Cli,
+    Profile(&'a str),
+    File,
+    /// The options of a tag, defined in the user config or not
+    Tag(&'a str, bool),
+    /// The options of a template, defined in the user config or not
+    Template(&'a str, bool),
+    Run(Option<&'a Source>),
+    User,
+}
+
+#[derive(Clone, Copy)]
+struct Layer<'a> {
+    origin: Origin<'a>,
+    options: Options<'a>,
+}
+
+struct Explainer<'a> {
+    matches: &'a ArgMatches,
+    cwd: PathBuf,
+    file: Option<PathBuf>,
+    /// Whether the config file sets the base working directory, which otherwise defaults to its
+    /// directory
+    file_workdir: bool,
+    spans: Option<Spans>,
+    user: Option<(PathBuf, Option<Spans>)>,
+}
+
+/// Prints the resolved options, the global ones and then the ones of each command matching the
+/// tags, with where each of them comes from: a flag, a `RUN_CLI_*` environment variable, the config
+/// file (with the line of the key for TOML files, literate or not, and of the run for the other
+/// formats), a profile, a tag, the run itself, or the default.
+pub fn explain(
+    options: &RunnerOptions,
+    layers: &Layers,
+    matches: &ArgMatches,
+) -> anyhow::Result<()> {
+    // the sources of the runs are absolute
+    let file = layers
+        .file
+        .as_ref()
+        .map(|(path, _)| path.canonicalize().unwrap_or_else(|_| path.clone()));
+    let explainer = Explainer {
+        matches,
+        cwd: std::env::current_dir()?,
+        file_workdir: file.as_deref().is_some_and(sets_workdir),
+        spans: file.as_deref().and_then(toml_spans),
+        file,
+        user: layers
+            .user
+            .as_ref()
+            .map(|(path, _)| (path.clone(), toml_spans(path))),
+    };
+
+    // the global configs, from the highest priority to the lowest
+    let profile = layers.cli.profile.as_deref().and_then(|name| {
+        let (_, config) = layers.file.as_ref()?;
+        Some((name, config.profiles.get(name)?))
+    });
+    let mut configs = vec![
+        (Origin::Set(None), &layers.overrides.global),
+        (Origin::Cli, &layers.cli),
+    ];
+    if let Some((name, profile)) = profile {
+        configs.push((Origin::Profile(name), profile));
+    }
+    if let Some((_, config)) = &layers.file {
+        configs.push((Origin::File, config));
+    }
+    if let Some((_, config)) = &layers.user {
+        configs.push((Origin::User, config));
+    }
+
+    let mut groups = vec![(
+        "global".to_string(),
+        explainer.global_rows(options, &configs),
+    )];
+    for (run, command) in layers.runs.iter().zip(&options.commands) {
+        let named = options.names.is_empty() || options.names.contains(&command.name);
+        if !named || !options.tags.matches(&command.tags) {
+            continue;
+        }
+        // the run takes precedence over its templates and its tags, the last one listed first, and
+        // then over the global options, except for the --set and cli/env ones. The runs hold these
+        // options, which come first to be told apart.
+        let overrides = layers
+            .overrides
+            .runs
+            .iter()
+            .find(|(name, _)| *name == command.name);
+        let mut run_layers: Vec<_> = overrides
+            .map(|(name, overrides)| Layer {
+                origin: Origin::Set(Some(name)),
+                options: Options::from(overrides),
+            })
+            .into_iter()
+            .collect();
+        run_layers.push(Layer {
+            origin: Origin::Set(None),
+            options: Options::over_runs(&layers.overrides.global),
+        });
+        run_layers.push(Layer {
+            origin: Origin::Cli,
+            options: Options::over_runs(&layers.cli),
+        });
+        run_layers.push(Layer {
+            origin: Origin::Run(run.command_source.as_ref()),
+            options: Options::from(run),
+        });
+        let templates = templates(run, &configs);
+        run_layers.extend(templates.iter().copied());
+        for tag in command.tags.iter().rev() {
+            for (origin, config) in &configs {
+                if let Some(options) = config.tag_options.get(tag) {
+                    run_layers.push(Layer {
+                        origin: Origin::Tag(tag, matches!(origin, Origin::User)),
+                        options: Options {
+                            cmd: &[],
+                            envs: &options.tag_envs,
+                            log: &options.tag_log,
+                            openai: &options.tag_openai,
+                            prefix: &options.tag_prefix,
+                            raw: options.tag_raw,
+                            workdir: options.tag_workdir.as_deref(),
+                        },
+                    });
+                }
+            }
+        }
+        run_layers.extend(configs.iter().map(|(origin, config)| Layer {
+            origin: *origin,
+            options: Options::from(*config),
+        }));
+        groups.push((
+            command.name.clone(),
+            explainer.command_rows(command, &run_layers),
+        ));
+    }
+
+    print!("{}", table::render(["OPTION", "VALUE", "SOURCE"], &groups)?);
+    Ok(())
+}
+
+impl Explainer<'_> {
+    /// The options which apply to all the commands, and cannot be set by the tags or the runs.
+    fn global_rows(
+        &self,
+        options: &RunnerOptions,
+        configs: &[(Origin, &Config)],
+    ) -> Vec<[String; 3]> {
+        let find = |keys: &[&str], is_set: fn(&Config) -> bool| {
+            configs
+                .iter()
+                .find(|(_, config)| is_set(config))
+                .map_or_else(
+                    || "default".into(),
+                    |(origin, _)| self.describe(origin, keys),
+                )
+        };
+
+        let mut rows = vec![];
+        if let Some((_, config)) = configs.iter().find(|(_, config)| config.profile.is_some()) {
+            rows.push([
+                "profile".into(),
+                config.profile.clone().unwrap_or_default(),
+                find(&["profile"], |config| config.profile.is_some()),
+            ]);
+        }
+
+        let mode = match options.mode {
+            RunnerMode::Sequential => "sequential",
+            RunnerMode::Parallel => "parallel",
+            RunnerMode::Tmux => "tmux",
+        };
+        rows.push([
+            "mode".into(),
+            mode.into(),
+            find(&["mode"], |c| c.mode.is_some()),
+        ]);
+
+        if let Some((_, config)) = configs.iter().find(|(_, config)| config.tags.is_some()) {
+            rows.push([
+                "tags".into(),
+                config.tags.clone().unwrap_or_default().join(","),
+                find(&["tags"], |config| config.tags.is_some()),
+            ]);
+        }
+        if let Some((_, config)) = configs
+            .iter()
+            .find(|(_, config)| config.exclude_tags.is_some())
+        {
+            rows.push([
+                "exclude_tags".into(),
+                config.exclude_tags.clone().unwrap_or_default().join(","),
+                find(&["exclude_tags"], |config| config.exclude_tags.is_some()),
+            ]);
+        }
+
+        let workdir = configs
+            .iter()
+            .find_map(|(_, config)| config.workdir.as_deref());
+        rows.push([
+            "workdir".into(),
+            self.display_path(workdir.unwrap_or(&self.cwd)),
+            configs
+                .iter()
+                .find(|(_, config)| config.workdir.is_some())
+                .map_or_else(
+                    || "default".into(),
+                    |(origin, _)| self.describe_workdir(origin),
+                ),
+        ]);
+
+        let tmux = &options.tmux;
+        rows.push([
+            "tmux.kill_duplicate_session".into(),
+            tmux.kill_duplicate_session.to_string(),
+            find(&["tmux", "kill_duplicate_session"], |config| {
+                config.tmux.tmux_kill_duplicate_session.is_some()
+            }),
+        ]);
+        rows.push([
+            "tmux.program".into(),
+            tmux.program.clone(),
+            find(&["tmux", "program"], |config| {
+                config.tmux.tmux_program.is_some()
+            }),
+        ]);
+        rows.push([
+            "tmux.session_prefix".into(),
+            tmux.session_prefix.clone(),
+            find(&["tmux", "session_prefix"], |config| {
+                config.tmux.tmux_session_prefix.is_some()
+            }),
+        ]);
+        rows.push([
+            "tmux.socket_path".into(),
+            tmux.socket_path.display().to_string(),
+            find(&["tmux", "socket_path"], |config| {
+                config.tmux.tmux_socket_path.is_some()
+            }),
+        ]);
+        rows
+    }
+
+    fn command_rows(&self, command: &RunnerCommand, layers: &[Layer]) -> Vec<[String; 3]> {
+        let mut rows = vec![];
+        rows.push([
+            "cmd".into(),
+            command.to_command_line(),
+            self.find(layers, &["cmd"], |options| !options.cmd.is_empty()),
+        ]);
+        rows.push([
+            "workdir".into(),
+            self.display_path(&command.workdir),
+            winner(layers, |options| options.workdir.is_some()).map_or_else(
+                || "default".into(),
+                |layer| self.describe_workdir(&layer.origin),
+            ),
+        ]);
+
+        // `raw` disables the processors, and `log.enabled` all the logs
+        let raw = winner(layers, |options| options.raw.is_some());
+        let raw = raw.filter(|layer| layer.options.raw.is_some_and(|raw| raw.unwrap_or(true)));
+        let raw_origin =
+            raw.map(|layer| format!("`raw` from {}", self.describe(&layer.origin, &["raw"])));
+        let log_disabled = winner(layers, |options| options.log.log_enabled.is_some())
+            .filter(|layer| layer.options.log.log_enabled == Some(Some(false)))
+            .map(|layer| {
+                format!(
+                    "`log.enabled` from {}",
+                    self.describe(&layer.origin, &["log", "enabled"])
+                )
+            });
+
+        for (key, value, is_set) in [
+            (
+                "spawns",
+                command.log.spawns,
+                (|log: &Log| log.log_spawns.is_some()) as fn(&Log) -> bool,
+            ),
+            ("terminations", command.log.terminations, |log: &Log| {
+                log.log_terminations.is_some()
+            }),
+        ] {
+            let origin = log_disabled
+                .clone()
+                .unwrap_or_else(|| self.find(layers, &["log", key], |options| is_set(options.log)));
+            rows.push([format!("log.{key}"), value.to_string(), origin]);
+        }
+
+        let prefix = matches!(command.prefix, RunnerPrefix::Enabled);
+        rows.push([
+            "prefix.enabled".into(),
+            prefix.to_string(),
+            raw_origin.clone().unwrap_or_else(|| {
+                self.find(layers, &["prefix", "enabled"], |options| {
+                    options.prefix.prefix_enabled.is_some()
+                })
+            }),
+        ]);
+        let openai = matches!(command.openai, RunnerOpenai::Enabled { .. });
+        rows.push([
+            "openai.enabled".into(),
+            openai.to_string(),
+            raw_origin.unwrap_or_else(|| {
+                self.find(layers, &["openai", "enabled"], |options| {
+                    options.openai.openai_enabled.is_some()
+                })
+            }),
+        ]);
+
+        // the variables are set from the lowest priority to the highest, the last value wins
+        let mut envs: Vec<(&str, &str, String)> = vec![];
+        for layer in layers.iter().rev() {
+            for kv in layer.options.envs {
+                let Some((key, _)) = kv.split_once('=') else {
+                    continue;
+                };
+                let origin = self.describe(&layer.origin, &["env"]);
+                envs.retain(|(other, _, _)| *other != key);
+                let value = command
+                    .envs
+                    .iter()
+                    .rev()
+                    .find(|(k, _)| k == key)
+                    .map_or("", |(_, v)| v.as_str());
+                envs.push((key, value, origin));
+            }
+        }
+        envs.sort_by_key(|(key, _, _)| *key);
+        for (key, value, origin) in envs {
+            rows.push([format!("env.{key}"), value.into(), origin]);
+        }
+        rows
+    }
+
+    fn find(&self, layers: &[Layer], keys: &[&str], is_set: impl Fn(&Options) -> bool) -> String {
+        winner(layers, is_set).map_or_else(
+            || "default".into(),
+            |layer| self.describe(&layer.origin, keys),
+        )
+    }
+
+    /// The base working directory of a config file is resolved to its directory when not set, so
+    /// the config file does not tell whether it has been set.
+    fn describe_workdir(&self, origin: &Origin) -> String {
+        match origin {
+            Origin::File if !self.file_workdir => "default (config directory)".into(),
+            origin => self.describe(origin, &["workdir"]),
+        }
+    }
+
+    fn describe(&self, origin: &Origin, keys: &[&str]) -> String {
+        match origin {
+            Origin::Set(None) => format!("--set {}", keys.join(".")),
+            Origin::Set(Some(name)) => format!("--set run.{name}.{}", keys.join(".")),
+            Origin::Cli => self.describe_cli(keys),
+            Origin::Profile(name) => {
+                let mut path = vec!["profile", name];
+                path.extend(keys);
+                format!("profile `{name}` ({})", self.locate(&path, None))
+            }
+            Origin::File => self.locate(keys, None),
+            Origin::Tag(name, false) => {
+                let mut path = vec!["tag", name];
+                path.extend(keys);
+                format!("tag `{name}` ({})", self.locate(&path, None))
+            }
+            Origin::Tag(name, true) => {
+                let mut path = vec!["tag", name];
+                path.extend(keys);
+                format!("tag `{name}` ({})", self.locate_user(&path))
+            }
+            Origin::Template(name, false) => {
+                let mut path = vec!["template", name];
+                path.extend(keys);
+                format!("template `{name}` ({})", self.locate(&path, None))
+            }
+            Origin::Template(name, true) => {
+                let mut path = vec!["template", name];
+                path.extend(keys);
+                format!("template `{name}` ({})", self.locate_user(&path))
+            }
+            Origin::Run(Some(source)) => self.locate(keys, Some(source)),
+            Origin::Run(None) => "command line".into(),
+            Origin::User => format!("user config ({})", self.locate_user(keys)),
+        }
+    }
+
+    /// Tells a flag from a `RUN_CLI_*` environment variable, whose argument ids are the names of
+    /// the fields of the config (e.g. `log_spawns`).
+    fn describe_cli(&self, keys: &[&str]) -> String {
+        let id = match keys {
+            ["env"] => "envs".to_string(),
+            keys => keys.join("_"),
+        };
+        let command = Cli::command();
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str());
+        match (self.matches.value_source(&id), arg) {
+            (Some(ValueSource::EnvVariable), Some(arg)) => {
+                arg.get_env().map_or("environment".into(), |env| {
+                    env.to_string_lossy().into_owned()
+                })
+            }
+            (_, Some(arg)) => arg
+                .get_long()
+                .map_or("command line".into(), |long| format!("--{long}")),
+            (_, None) => "command line".into(),
+        }
+    }
+
+    /// The location of a key of the config file, or of a run, as `path:line`. The line of the key
+    /// setting the option is only known for TOML files (literate or not), only the line starting
+    /// the run is known for the other formats.
+    fn locate(&self, keys: &[&str], run: Option<&Source>) -> String {
+        let Some(path) = run
+            .map(|source| source.path.as_path())
+            .or(self.file.as_deref())
+        else {
+            return "command line".into();
+        };
+        match (self.spans.as_ref(), run) {
+            // the keys are only known in TOML, the runs are located in all the formats
+            (None, Some(source)) => {
+                let line = std::fs::read_to_string(path).ok().and_then(|content| {
+                    Some(content.get(..source.span.start)?.matches('\n').count() + 1)
+                });
+                match line {
+                    Some(line) => format!("{}:{line}", self.display_path(path)),
+                    None => self.display_path(path),
+                }
+            }
+            (spans, _) => self.locate_in(path, spans, keys, run),
+        }
+    }
+
+    fn locate_user(&self, keys: &[&str]) -> String {
+        match &self.user {
+            Some((path, spans)) => self.locate_in(path, spans.as_ref(), keys, None),
+            None => "user config".into(),
+        }
+    }
+
+    fn locate_in(
+        &self,
+        path: &Path,
+        spans: Option<&Spans>,
+        keys: &[&str],
+        run: Option<&Source>,
+    ) -> String {
+        let offset = spans.and_then(|spans| {
+            let mut offset = run.map(|source| source.span.start);
+            // the runnable code blocks are located as a whole, their keys are generated
+            let mut node = match run {
+                Some(source) => spans.find_run(source),
+                None => Some(&spans.root),
+            };
+            for key in keys {
+                let Some((span, child)) = node.and_then(|node| node.get(key)) else {
+                    break;
+                };
+                offset = Some(spans.source_map.map(span.start));
+                node = Some(child);
+            }
+            Some(spans.content.get(..offset?)?.matches('\n').count() + 1)
+        });
+        match offset {
+            Some(line) => format!("{}:{line}", self.display_path(path)),
+            None => self.display_path(path),
+        }
+    }
+
+    fn display_path(&self, path: &Path) -> String {
+        relative_path(path, &self.cwd).display().to_string()
+    }
+}
+
+/// The layers of the templates extended by the run, the nearest first, each template being
+/// possibly defined by several configs.
+fn templates<'a>(run: &'a Command, configs: &[(Origin<'a>, &'a Config)]) -> Vec<Layer<'a>> {
+    let mut layers = vec![];
+    let mut chain: Vec<&str> = vec![];
+    let mut extends = run.command_extends.as_deref();
+    // a cycle fails to resolve the run before getting here, but this must not loop forever
+    while let Some(name) = extends.filter(|name| !chain.contains(name)) {
+        chain.push(name);
+        extends = None;
+        for (origin, config) in configs {
+            if let Some(template) = config.templates.get(name) {
+                extends = extends.or(template.command_extends.as_deref());
+                layers.push(Layer {
+                    origin: Origin::Template(name, matches!(origin, Origin::User)),
+                    options: Options::from(template),
+                });
+            }
+        }
+    }
+    layers
+}
+
+/// The first layer setting the option, the layers being sorted by priority.
+fn winner<'l, 'a>(
+    layers: &'l [Layer<'a>],
+    is_set: impl Fn(&Options) -> bool,
+) -> Option<&'l Layer<'a>> {
+    layers.iter().find(|layer| is_set(&layer.options))
+}
+
+/// The tree of the keys of a TOML file, with their spans. The spans of the values are not
+/// known for the tables implicitly declared by a header (e.g. `profile` for `[profile.ci]`).
+enum Node {
+    Table(Vec<(Spanned<String>, Node)>),
+    Array(Vec<Node>),
+    Value,
+}
+
+impl Node {
+    fn get(&self, key: &str) -> Option<(std::ops::Range<usize>, &Node)> {
+        match self {
+            Node::Table(entries) => entries
+                .iter()
+                .find(|(k, _)| k.get_ref() == key)
+                .map(|(k, v)| (k.span(), v)),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodeVisitor;
+
+        impl<'de> Visitor<'de> for NodeVisitor {
+            type Value = Node;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a TOML value")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+                let mut entries = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Node::Table(entries))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+                let mut items = vec![];
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Node::Array(items))
+            }
+
+            fn visit_bool<E>(self, _: bool) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_i64<E>(self, _: i64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_u64<E>(self, _: u64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_f64<E>(self, _: f64) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+
+            fn visit_str<E>(self, _: &str) -> Result<Node, E> {
+                Ok(Node::Value)
+            }
+        }
+
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+/// The keys of a TOML config file, the spans of the literate ones being mapped back to the file
+/// with the source map of their TOML document.
+struct Spans {
+    content: String,
+    root: Node,
+    source_map: SourceMap,
+}
+
+impl Spans {
+    /// The `[[run]]` table of a run, whose keys are within its span.
+    fn find_run(&self, source: &Source) -> Option<&Node> {
+        let (_, Node::Array(runs)) = self.root.get("run")? else {
+            return None;
+        };
+        runs.iter().find(|run| match run {
+            Node::Table(entries) => entries.first().is_some_and(|(key, _)| {
+                source.span.contains(&self.source_map.map(key.span().start))
+            }),
+            _ => false,
+        })
+    }
+}
+
+/// Parses the keys of a TOML config file, literate or not.
+fn toml_spans(path: &Path) -> Option<Spans> {
+    let content = std::fs::read_to_string(path).ok()?;
+    let (toml_str, source_map) = Config::toml_document(path, &content)?;
+    let root = toml::from_str(&toml_str).ok()?;
+    Some(Spans {
+        content,
+        root,
+        source_map,
+    })
+}
+
+/// Whether the config file sets the base working directory, as read before it gets resolved.
+fn sets_workdir(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| Config::parse_str(path, &content).ok())
+        .is_some_and(|config| config.workdir.is_some())
+}
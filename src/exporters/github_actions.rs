@@ -0,0 +1,112 @@
+use super::unique_names;
+use crate::paths::relative_path;
+use crate::runner::{RunnerCommand, RunnerMode};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[derive(Serialize)]
+struct Workflow {
+    name: &'static str,
+    on: [&'static str; 2],
+    jobs: serde_norway::Mapping,
+}
+
+#[derive(Serialize)]
+struct Job {
+    #[serde(rename = "runs-on")]
+    runs_on: &'static str,
+    steps: Vec<Step>,
+}
+
+#[derive(Serialize)]
+struct Step {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uses: Option<&'static str>,
+    #[serde(rename = "working-directory", skip_serializing_if = "Option::is_none")]
+    working_directory: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    env: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    run: Option<String>,
+}
+
+const CHECKOUT: Step = Step {
+    name: None,
+    uses: Some("actions/checkout@v4"),
+    working_directory: None,
+    env: BTreeMap::new(),
+    run: None,
+};
+
+/// Returns a workflow running the commands on push and pull requests. In the sequential mode, a
+/// single job runs a step per command. In the parallel and tmux modes, each command gets its own
+/// job, so that they run concurrently. The working directories are relative to `root`, the root of
+/// the repository, where the steps start.
+pub(super) fn export(
+    commands: &[RunnerCommand],
+    mode: &RunnerMode,
+    root: &Path,
+) -> anyhow::Result<String> {
+    let steps = commands.iter().map(|cmd| step(cmd, root));
+
+    let mut jobs = serde_norway::Mapping::new();
+    match mode {
+        RunnerMode::Sequential => {
+            let job = Job {
+                runs_on: "ubuntu-latest",
+                steps: std::iter::once(CHECKOUT).chain(steps).collect(),
+            };
+            jobs.insert("run".into(), serde_norway::to_value(job)?);
+        }
+        RunnerMode::Parallel | RunnerMode::Tmux => {
+            // the job ids must start with a letter or `_`
+            let names = unique_names(
+                commands,
+                |c| c.is_ascii_alphanumeric() || c == '_' || c == '-',
+                |c| c.is_ascii_alphabetic() || c == '_',
+            );
+            for (name, step) in names.into_iter().zip(steps) {
+                let job = Job {
+                    runs_on: "ubuntu-latest",
+                    steps: vec![CHECKOUT, step],
+                };
+                jobs.insert(name.into(), serde_norway::to_value(job)?);
+            }
+        }
+    }
+
+    let workflow = Workflow {
+        name: "run",
+        on: ["push", "pull_request"],
+        jobs,
+    };
+    Ok(format!(
+        "# Generated by `run export github-actions`\n{}",
+        serde_norway::to_string(&workflow)?
+    ))
+}
+
+fn step(cmd: &RunnerCommand, root: &Path) -> Step {
+    let workdir = relative_path(&cmd.workdir, root);
+    Step {
+        name: Some(
+            cmd.description
+                .as_deref()
+                .and_then(|description| description.lines().next())
+                .unwrap_or(&cmd.name)
+                .to_string(),
+        ),
+        uses: None,
+        working_directory: Some(workdir.to_string_lossy().into_owned())
+            .filter(|workdir| workdir != "."),
+        // the last value of a variable wins
+        env: cmd.envs.iter().cloned().collect(),
+        run: Some(match cmd.shell_path() {
+            Some(path) => format!("{path} {}", cmd.to_command_line()),
+            None => cmd.to_command_line(),
+        }),
+    }
+}
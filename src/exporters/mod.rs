@@ -0,0 +1,205 @@
+mod github_actions;
+mod procfile;
+mod sh;
+mod systemd;
+
+use crate::paths::repository_root;
+use crate::runner::{filter_commands, RunnerCommand, RunnerOptions};
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+pub use systemd::{SystemdGroupBy, SystemdRestart};
+
+#[derive(clap::Subcommand)]
+pub enum Exporter {
+    #[command(about = "Write a systemd user service per command, and targets grouping them")]
+    Systemd {
+        #[arg(
+            long,
+            required_unless_present = "stdout",
+            help = "The directory to write the units to (e.g. ~/.config/systemd/user)"
+        )]
+        out: Option<PathBuf>,
+
+        #[arg(long, help = "Print the units on stdout instead of writing them")]
+        stdout: bool,
+
+        #[arg(
+            long,
+            help = "The name of the project, prefixing the units (defaults to the name of the current directory)"
+        )]
+        name: Option<String>,
+
+        #[arg(
+            long = "group-by",
+            value_enum,
+            default_value_t,
+            help = "Write a single target for the project, or a target per tag"
+        )]
+        group_by: SystemdGroupBy,
+
+        #[arg(
+            long,
+            value_enum,
+            default_value_t,
+            help = "The restart policy of the services"
+        )]
+        restart: SystemdRestart,
+    },
+
+    #[command(about = "Print a Procfile, for foreman-compatible hosts")]
+    Procfile {
+        #[arg(long, help = "Write the Procfile to OUT instead of stdout")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(about = "Print a POSIX shell script running the commands one after the other")]
+    Sh {
+        #[arg(long, help = "Write the script to OUT instead of stdout")]
+        out: Option<PathBuf>,
+    },
+
+    #[command(
+        about = "Print a GitHub Actions workflow, with a step per command (or a job per command in the parallel and tmux modes)"
+    )]
+    GithubActions {
+        #[arg(long, help = "Write the workflow to OUT instead of stdout")]
+        out: Option<PathBuf>,
+    },
+}
+
+/// A file produced by an exporter.
+struct Export {
+    file_name: String,
+    content: String,
+}
+
+/// Exports the commands matching the tags to the format of another tool.
+pub fn export(options: RunnerOptions, exporter: Exporter) -> anyhow::Result<()> {
+    let commands = filter_commands(options.commands, &options.tags, &options.names);
+    let cwd = std::env::current_dir()?;
+    // the files are run from their directory, or the base working directory when printed
+    let base = |out: &Option<PathBuf>| {
+        let dir = match out {
+            Some(out) => cwd
+                .join(out)
+                .parent()
+                .map_or_else(|| cwd.clone(), Path::to_owned),
+            None => options.workdir.clone(),
+        };
+        dir.canonicalize().unwrap_or(dir)
+    };
+
+    match exporter {
+        Exporter::Systemd {
+            out,
+            stdout,
+            name,
+            group_by,
+            restart,
+        } => {
+            let name = match name {
+                Some(name) => name,
+                None => current_dir_name()?,
+            };
+            let files = systemd::export(&commands, &name, &group_by, &restart);
+            if stdout {
+                print_files(&files);
+            } else if let Some(out) = out {
+                write_files(&out, &files)?;
+                println!(
+                    "Reload and start the units with: systemctl --user daemon-reload && systemctl --user start {}",
+                    files
+                        .iter()
+                        .filter(|file| file.file_name.ends_with(".target"))
+                        .map(|file| file.file_name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                );
+            }
+        }
+        Exporter::Procfile { out } => {
+            let procfile = procfile::export(&commands, &base(&out));
+            write_or_print(out, &procfile)?;
+        }
+        Exporter::Sh { out } => write_or_print(out.clone(), &sh::export(&commands, &base(&out)))?,
+        Exporter::GithubActions { out } => {
+            // the steps start at the root of the checked out repository
+            let root = repository_root(&options.workdir).unwrap_or_else(|| cwd.clone());
+            let workflow = github_actions::export(&commands, &options.mode, &root)?;
+            write_or_print(out, &workflow)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns a name per command, made of the characters accepted by `is_valid`, starting with a
+/// character accepted by `is_valid_start` (or prefixed with `_`), and unique among the commands
+/// (e.g. `node`, `node-2`).
+fn unique_names(
+    commands: &[RunnerCommand],
+    is_valid: fn(char) -> bool,
+    is_valid_start: fn(char) -> bool,
+) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for cmd in commands {
+        let mut base: String = cmd
+            .name
+            .chars()
+            .map(|c| if is_valid(c) { c } else { '-' })
+            .collect();
+        if !base.starts_with(is_valid_start) {
+            base.insert(0, '_');
+        }
+        let mut name = base.clone();
+        let mut i = 1;
+        while names.contains(&name) {
+            i += 1;
+            name = format!("{base}-{i}");
+        }
+        names.push(name);
+    }
+    names
+}
+
+fn current_dir_name() -> anyhow::Result<String> {
+    let cwd = std::env::current_dir()?;
+    Ok(cwd.file_name().map_or_else(
+        || "run".to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    ))
+}
+
+fn write_or_print(out: Option<PathBuf>, content: &str) -> anyhow::Result<()> {
+    match out {
+        Some(out) => {
+            std::fs::write(&out, content)
+                .with_context(|| format!("failed to write {}", out.display()))?;
+            println!("Wrote {}", out.display());
+        }
+        None => print!("{content}"),
+    }
+    Ok(())
+}
+
+fn print_files(files: &[Export]) {
+    for (i, file) in files.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("==> {} <==", file.file_name);
+        print!("{}", file.content);
+    }
+}
+
+fn write_files(out: &Path, files: &[Export]) -> anyhow::Result<()> {
+    std::fs::create_dir_all(out).with_context(|| format!("failed to create {}", out.display()))?;
+    for file in files {
+        let path = out.join(&file.file_name);
+        std::fs::write(&path, &file.content)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,42 @@
+use super::unique_names;
+use crate::paths::relative_path;
+use crate::runner::RunnerCommand;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Returns a Procfile with a process per command. As the processes are started from the directory
+/// of the Procfile, `dir`, they change to their working directory and set their environment
+/// variables first.
+pub(super) fn export(commands: &[RunnerCommand], dir: &Path) -> String {
+    let mut procfile = String::new();
+    let names = unique_names(commands, is_process_char, is_process_char);
+    for (name, cmd) in names.iter().zip(commands) {
+        let _ = writeln!(procfile, "{name}: {}", command_line(cmd, dir));
+    }
+    procfile
+}
+
+/// The command line of a command, run from `dir`, e.g. `cd api && PORT=3000 node .`.
+fn command_line(cmd: &RunnerCommand, dir: &Path) -> String {
+    let mut line = String::new();
+    let workdir = relative_path(&cmd.workdir, dir);
+    if workdir != Path::new(".") {
+        let _ = write!(
+            line,
+            "cd {} && ",
+            shell_words::quote(&workdir.to_string_lossy())
+        );
+    }
+    for (key, value) in &cmd.envs {
+        let _ = write!(line, "{key}={} ", shell_words::quote(value));
+    }
+    if let Some(path) = cmd.shell_path() {
+        let _ = write!(line, "{path} ");
+    }
+    line.push_str(&cmd.to_command_line());
+    line
+}
+
+fn is_process_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
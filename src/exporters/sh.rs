@@ -0,0 +1,38 @@
+use crate::paths::relative_path;
+use crate::runner::RunnerCommand;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Returns a POSIX shell script running the commands one after the other, and stopping at the
+/// first failure. Each command runs in a subshell, so that its working directory and environment
+/// variables don't leak to the next ones. The working directories are relative to the directory of
+/// the script, `dir`, which the script changes to first.
+pub(super) fn export(commands: &[RunnerCommand], dir: &Path) -> String {
+    let mut script = String::from(
+        "#!/bin/sh\n# Generated by `run export sh`\nset -eu\ncd \"$(dirname \"$0\")\"\n",
+    );
+    for cmd in commands {
+        let _ = write!(script, "\n# {}", cmd.name);
+        if let Some(description) = cmd.description.as_deref().and_then(|d| d.lines().next()) {
+            let _ = write!(script, ": {description}");
+        }
+        script.push_str("\n(\n");
+
+        let workdir = relative_path(&cmd.workdir, dir);
+        if workdir != Path::new(".") {
+            let _ = writeln!(
+                script,
+                "  cd {}",
+                shell_words::quote(&workdir.to_string_lossy())
+            );
+        }
+        for (key, value) in &cmd.envs {
+            let _ = writeln!(script, "  export {key}={}", shell_words::quote(value));
+        }
+        if let Some(path) = cmd.shell_path() {
+            let _ = writeln!(script, "  export {path}");
+        }
+        let _ = writeln!(script, "  {}\n)", cmd.to_command_line());
+    }
+    script
+}
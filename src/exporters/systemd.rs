@@ -0,0 +1,176 @@
+use super::{unique_names, Export};
+use crate::runner::RunnerCommand;
+use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Default, ValueEnum)]
+pub enum SystemdGroupBy {
+    #[default]
+    Config,
+    Tag,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum)]
+pub enum SystemdRestart {
+    No,
+    #[default]
+    OnFailure,
+    Always,
+}
+
+impl SystemdRestart {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::No => "no",
+            Self::OnFailure => "on-failure",
+            Self::Always => "always",
+        }
+    }
+}
+
+const HEADER: &str = "# Generated by `run export systemd`";
+
+/// Returns a `.service` unit per command, and the `.target` units grouping them: a single one for
+/// the project, or one per tag. The services are part of their targets, so that stopping or
+/// restarting a target applies to all its services.
+pub(super) fn export(
+    commands: &[RunnerCommand],
+    project: &str,
+    group_by: &SystemdGroupBy,
+    restart: &SystemdRestart,
+) -> Vec<Export> {
+    let project = unit_name(project);
+    let services: Vec<(String, &RunnerCommand)> =
+        unique_names(commands, is_unit_char, is_unit_char)
+            .into_iter()
+            .map(|name| format!("run-{project}-{name}.service"))
+            .zip(commands)
+            .collect();
+
+    let mut targets: BTreeMap<String, (String, Vec<&str>)> = BTreeMap::new();
+    for (service, cmd) in &services {
+        let groups = match group_by {
+            SystemdGroupBy::Config => vec![(
+                format!("run-{project}.target"),
+                format!("The commands of {project}"),
+            )],
+            SystemdGroupBy::Tag if cmd.tags.is_empty() => vec![(
+                format!("run-{project}-untagged.target"),
+                format!("The untagged commands of {project}"),
+            )],
+            SystemdGroupBy::Tag => cmd
+                .tags
+                .iter()
+                .map(|tag| {
+                    (
+                        format!("run-{project}-{}.target", unit_name(tag)),
+                        format!("The {tag} commands of {project}"),
+                    )
+                })
+                .collect(),
+        };
+        for (target, description) in groups {
+            targets
+                .entry(target)
+                .or_insert_with(|| (description, vec![]))
+                .1
+                .push(service);
+        }
+    }
+
+    let mut files: Vec<Export> = services
+        .iter()
+        .map(|(service, cmd)| {
+            let part_of: Vec<&str> = targets
+                .iter()
+                .filter(|(_, (_, wants))| wants.contains(&service.as_str()))
+                .map(|(target, _)| target.as_str())
+                .collect();
+            Export {
+                file_name: service.clone(),
+                content: render_service(cmd, &part_of, restart),
+            }
+        })
+        .collect();
+    files.extend(targets.iter().map(|(target, (description, wants))| Export {
+        file_name: target.clone(),
+        content: format!(
+            "{HEADER}\n[Unit]\nDescription={}\nWants={}\n\n[Install]\nWantedBy=default.target\n",
+            escape_specifiers(description),
+            wants.join(" ")
+        ),
+    }));
+    files
+}
+
+fn render_service(cmd: &RunnerCommand, part_of: &[&str], restart: &SystemdRestart) -> String {
+    let description = cmd
+        .description
+        .as_deref()
+        .and_then(|description| description.lines().next())
+        .unwrap_or(&cmd.name);
+
+    let mut unit = format!(
+        "{HEADER}\n[Unit]\nDescription={}\nPartOf={}\n\n[Service]\n",
+        escape_specifiers(description),
+        part_of.join(" ")
+    );
+    // systemd does not search the PATH of the user, the programs are resolved at export time
+    let program = resolve_program(cmd);
+    let command_line = shell_words::join(
+        std::iter::once(program.as_str()).chain(cmd.args.iter().map(String::as_str)),
+    );
+    // systemd cannot prepend to the PATH, the shell does it before executing the command
+    let command_line = match cmd.shell_path() {
+        Some(path) => format!(
+            "/bin/sh -c {} sh {command_line}",
+            shell_words::quote(&format!("{path} exec \"$@\""))
+        ),
+        None => command_line,
+    };
+    // `$` would be expanded by systemd, `%` are specifiers
+    let _ = writeln!(
+        unit,
+        "ExecStart={}",
+        escape_specifiers(&command_line).replace('$', "$$")
+    );
+    let _ = writeln!(unit, "WorkingDirectory={}", cmd.workdir.display());
+    for (key, value) in &cmd.envs {
+        let env = format!("{key}={value}")
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        let _ = writeln!(unit, "Environment=\"{}\"", escape_specifiers(&env));
+    }
+    let _ = writeln!(unit, "Restart={}", restart.as_str());
+    unit
+}
+
+/// Returns the absolute path of the program of a command, found in its PATH. The programs which
+/// cannot be found are kept as is, with a warning.
+fn resolve_program(cmd: &RunnerCommand) -> String {
+    if let Some(found) = cmd.find_program() {
+        return found.to_string_lossy().into_owned();
+    }
+    eprintln!(
+        "warning: the program `{}` was not found, ExecStart= requires an absolute path",
+        cmd.program
+    );
+    cmd.program.clone()
+}
+
+/// Replaces the characters which are not allowed in unit names.
+fn unit_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if is_unit_char(c) { c } else { '-' })
+        .collect()
+}
+
+fn is_unit_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.-".contains(c)
+}
+
+fn escape_specifiers(value: &str) -> String {
+    value.replace('%', "%%")
+}
@@ -0,0 +1,336 @@
+use crate::loaders::{compose, Loader};
+use anyhow::{bail, Context};
+use std::fmt::Write;
+use std::path::Path;
+
+/// A group of commands detected from a single project file.
+struct Section {
+    title: String,
+    description: String,
+    runs: Vec<Run>,
+}
+
+struct Run {
+    name: String,
+    description: Option<String>,
+    cmd: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl Run {
+    fn new<S: AsRef<str>>(name: &str, cmd: &[S], tag: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: None,
+            cmd: cmd.iter().map(|s| s.as_ref().to_string()).collect(),
+            tags: vec![tag.to_string()],
+        }
+    }
+
+    fn describe<S: Into<String>>(mut self, description: S) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+/// Writes a starter config in `dir`, with the commands detected from the project files (scripts
+/// of package.json, Makefile targets, etc.).
+pub async fn init(dir: &Path, literate: bool, force: bool, stdout: bool) -> anyhow::Result<()> {
+    let mut sections = [
+        detect_package_json,
+        detect_cargo,
+        detect_go,
+        detect_makefile,
+    ]
+    .into_iter()
+    .map(|detect| detect(dir))
+    .collect::<anyhow::Result<Vec<_>>>()?;
+    // the files which can be run as is are read by their loader
+    sections.push(detect_procfile(dir).await?);
+    sections.push(detect_compose(dir).await?);
+    let mut sections: Vec<_> = sections.into_iter().flatten().collect();
+
+    if sections.is_empty() {
+        bail!(
+            "no project tooling detected in {} (looked for package.json, Cargo.toml, go.mod, Makefile, Procfile and docker compose files)",
+            dir.display()
+        );
+    }
+    disambiguate(&mut sections);
+
+    let config = if literate {
+        render_literate(&sections)?
+    } else {
+        render_toml(&sections)?
+    };
+
+    if stdout {
+        print!("{config}");
+        return Ok(());
+    }
+
+    let path = dir.join(if literate { "run.toml.md" } else { "run.toml" });
+    if path.exists() && !force {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            path.display()
+        );
+    }
+    std::fs::write(&path, config).with_context(|| format!("failed to write {}", path.display()))?;
+
+    let count: usize = sections.iter().map(|section| section.runs.len()).sum();
+    let plural = if count == 1 { "" } else { "s" };
+    println!("Created {} with {count} command{plural}", path.display());
+    Ok(())
+}
+
+/// Prefixes the names shared by several runs (e.g. the `build` of cargo and of go) with their tag,
+/// so that each run keeps a unique name.
+fn disambiguate(sections: &mut [Section]) {
+    let names: Vec<String> = sections
+        .iter()
+        .flat_map(|section| &section.runs)
+        .map(|run| run.name.clone())
+        .collect();
+    for run in sections.iter_mut().flat_map(|section| &mut section.runs) {
+        if names.iter().filter(|name| **name == run.name).count() > 1 {
+            if let Some(tag) = run.tags.first() {
+                run.name = format!("{tag}-{}", run.name);
+            }
+        }
+    }
+}
+
+fn render_toml(sections: &[Section]) -> Result<String, std::fmt::Error> {
+    let mut config =
+        String::from("# Generated by `run init`, list the commands with `run --list`\n");
+    for section in sections {
+        write!(config, "\n# {}\n", section.title)?;
+        config.push_str(&render_runs(&section.runs)?);
+    }
+    Ok(config)
+}
+
+fn render_literate(sections: &[Section]) -> Result<String, std::fmt::Error> {
+    let mut config = String::from(
+        "# Runfile\n\nGenerated by `run init`, list the commands with `run --list`.\n",
+    );
+    for section in sections {
+        write!(
+            config,
+            "\n## {}\n\n{}\n\n```toml\n{}```\n",
+            section.title,
+            section.description,
+            render_runs(&section.runs)?.trim_start()
+        )?;
+    }
+    Ok(config)
+}
+
+fn render_runs(runs: &[Run]) -> Result<String, std::fmt::Error> {
+    let string = |s: &str| toml::Value::from(s).to_string();
+    let array = |values: &[String]| {
+        toml::Value::Array(values.iter().map(|v| v.as_str().into()).collect()).to_string()
+    };
+
+    let mut toml = String::new();
+    for run in runs {
+        write!(toml, "\n[[run]]\nname = {}\n", string(&run.name))?;
+        if let Some(description) = &run.description {
+            writeln!(toml, "description = {}", string(description))?;
+        }
+        writeln!(toml, "cmd = {}", array(&run.cmd))?;
+        writeln!(toml, "tags = {}", array(&run.tags))?;
+    }
+    Ok(toml)
+}
+
+fn detect_package_json(dir: &Path) -> anyhow::Result<Option<Section>> {
+    let Some(content) = read_optional(&dir.join("package.json"))? else {
+        return Ok(None);
+    };
+    let package: serde_json::Value =
+        serde_json::from_str(&content).context("failed to parse package.json")?;
+
+    let manager = if dir.join("pnpm-lock.yaml").is_file() {
+        "pnpm"
+    } else if dir.join("yarn.lock").is_file() {
+        "yarn"
+    } else {
+        "npm"
+    };
+
+    let runs: Vec<_> = package
+        .get("scripts")
+        .and_then(serde_json::Value::as_object)
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, script)| {
+            let cmd: &[&str] = match manager {
+                "yarn" => &["yarn", name],
+                _ => &[manager, "run", name],
+            };
+            Some(Run::new(name, cmd, "node").describe(script.as_str()?))
+        })
+        .collect();
+
+    Ok(Some(Section {
+        title: format!("{manager} scripts"),
+        description: "The scripts of package.json.".into(),
+        runs,
+    })
+    .filter(|section| !section.runs.is_empty()))
+}
+
+#[allow(clippy::unnecessary_wraps)] // same signature as the other detectors
+fn detect_cargo(dir: &Path) -> anyhow::Result<Option<Section>> {
+    if !dir.join("Cargo.toml").is_file() {
+        return Ok(None);
+    }
+
+    let mut runs = vec![
+        Run::new("build", &["cargo", "build"], "rust").describe("Build the crate"),
+        Run::new("test", &["cargo", "test"], "rust").describe("Run the tests"),
+    ];
+    if dir.join("src/main.rs").is_file() {
+        runs.push(Run::new("run", &["cargo", "run"], "rust").describe("Run the binary"));
+    }
+
+    Ok(Some(Section {
+        title: "Rust".into(),
+        description: "The cargo commands of Cargo.toml.".into(),
+        runs,
+    }))
+}
+
+#[allow(clippy::unnecessary_wraps)] // same signature as the other detectors
+fn detect_go(dir: &Path) -> anyhow::Result<Option<Section>> {
+    if !dir.join("go.mod").is_file() {
+        return Ok(None);
+    }
+
+    let mut runs = vec![
+        Run::new("build", &["go", "build", "./..."], "go").describe("Build all the packages"),
+        Run::new("test", &["go", "test", "./..."], "go").describe("Run the tests"),
+    ];
+    if dir.join("main.go").is_file() {
+        runs.push(Run::new("run", &["go", "run", "."], "go").describe("Run the main package"));
+    }
+
+    Ok(Some(Section {
+        title: "Go".into(),
+        description: "The go commands of go.mod.".into(),
+        runs,
+    }))
+}
+
+/// The targets are the rules without variables nor patterns. A comment right above a target is
+/// used as its description.
+fn detect_makefile(dir: &Path) -> anyhow::Result<Option<Section>> {
+    let Some(content) = read_optional(&dir.join("Makefile"))? else {
+        return Ok(None);
+    };
+
+    let mut runs: Vec<Run> = vec![];
+    let mut comment: Option<&str> = None;
+    for line in content.lines() {
+        if let Some(text) = line.strip_prefix('#') {
+            comment = Some(text.trim_start_matches('#').trim());
+            continue;
+        }
+
+        let target = Some(line)
+            .filter(|line| !line.starts_with(char::is_whitespace)) // recipes
+            .and_then(|line| line.split_once(':'))
+            // the variables assigned with `:=`, `::=` or `:::=`, unlike the `::` rules
+            .filter(|(_, rest)| !rest.trim_start_matches(':').starts_with('='))
+            .map(|(target, _)| target.trim())
+            .filter(|target| {
+                !target.is_empty()
+                    && !target.starts_with('.')
+                    && target
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "_-./".contains(c))
+            });
+        if let Some(target) = target {
+            if !runs.iter().any(|run| run.name == target) {
+                let run = Run::new(target, &["make", target], "make");
+                runs.push(match comment {
+                    Some(comment) => run.describe(comment),
+                    None => run,
+                });
+            }
+        }
+        comment = None;
+    }
+
+    Ok(Some(Section {
+        title: "Makefile".into(),
+        description: "The targets of the Makefile.".into(),
+        runs,
+    })
+    .filter(|section| !section.runs.is_empty()))
+}
+
+async fn detect_procfile(dir: &Path) -> anyhow::Result<Option<Section>> {
+    let path = dir.join("Procfile");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let (config, _) = Loader::Procfile.load(&path).await?;
+
+    let runs = config
+        .runs
+        .into_iter()
+        .filter_map(|run| Some((run.command_name?, run.command_cmd)))
+        .map(|(name, cmd)| {
+            Run::new(&name, &cmd, "procfile")
+                .describe(format!("The {name} process of the Procfile"))
+        })
+        .collect();
+
+    Ok(Some(Section {
+        title: "Procfile".into(),
+        description: "The processes of the Procfile.".into(),
+        runs,
+    })
+    .filter(|section| !section.runs.is_empty()))
+}
+
+/// The services are started with docker compose, the ones without a `command` included, which
+/// the compose loader skips as they can't be run natively.
+async fn detect_compose(dir: &Path) -> anyhow::Result<Option<Section>> {
+    const FILE_NAMES: [&str; 4] = [
+        "compose.yaml",
+        "compose.yml",
+        "docker-compose.yaml",
+        "docker-compose.yml",
+    ];
+    let Some(file_name) = FILE_NAMES.into_iter().find(|name| dir.join(name).is_file()) else {
+        return Ok(None);
+    };
+
+    let runs = compose::services(&dir.join(file_name))
+        .await?
+        .iter()
+        .map(|service| {
+            Run::new(service, &["docker", "compose", "up", service], "docker")
+                .describe(format!("Start the {service} service"))
+        })
+        .collect();
+
+    Ok(Some(Section {
+        title: "Docker Compose".into(),
+        description: format!("The services of {file_name}."),
+        runs,
+    })
+    .filter(|section| !section.runs.is_empty()))
+}
+
+fn read_optional(path: &Path) -> anyhow::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
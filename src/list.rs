@@ -0,0 +1,109 @@
+use crate::paths::relative_path;
+use crate::runner::{filter_commands, RunnerCommand, RunnerOptions};
+use crate::table;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, ValueEnum)]
+pub enum ListFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, Default, ValueEnum)]
+pub enum ListGroupBy {
+    #[default]
+    File,
+    Tag,
+}
+
+#[derive(Serialize)]
+struct ListItem<'a> {
+    name: &'a str,
+    description: Option<&'a str>,
+    tags: &'a [String],
+    workdir: PathBuf,
+    command: String,
+    file: Option<PathBuf>,
+}
+
+/// Prints the commands that would be run, after tag filtering. The json format is a flat list,
+/// the grouping only applies to the table format.
+pub fn list(
+    options: RunnerOptions,
+    format: &ListFormat,
+    group_by: &ListGroupBy,
+) -> anyhow::Result<()> {
+    let commands = filter_commands(options.commands, &options.tags, &options.names);
+    let cwd = std::env::current_dir()?;
+
+    let items: Vec<_> = commands
+        .iter()
+        .map(|cmd| ListItem {
+            name: &cmd.name,
+            description: cmd.description.as_deref(),
+            tags: &cmd.tags,
+            workdir: relative_path(&cmd.workdir, &cwd),
+            command: cmd.to_command_line(),
+            file: cmd
+                .source
+                .as_ref()
+                .map(|source| relative_path(&source.path, &cwd)),
+        })
+        .collect();
+
+    match format {
+        ListFormat::Json => serde_json::to_writer_pretty(std::io::stdout(), &items)?,
+        ListFormat::Table => print!("{}", format_table(&items, &commands, group_by)?),
+    }
+
+    Ok(())
+}
+
+fn format_table(
+    items: &[ListItem],
+    commands: &[RunnerCommand],
+    group_by: &ListGroupBy,
+) -> Result<String, std::fmt::Error> {
+    let mut groups: BTreeMap<String, Vec<[String; 5]>> = BTreeMap::new();
+    for (item, cmd) in items.iter().zip(commands) {
+        let row = [
+            item.name.to_string(),
+            // only the first line of multi-line descriptions (e.g. from literate configs)
+            item.description
+                .and_then(|description| description.lines().next())
+                .unwrap_or("-")
+                .to_string(),
+            if item.tags.is_empty() {
+                "-".to_string()
+            } else {
+                item.tags.join(",")
+            },
+            item.workdir.display().to_string(),
+            item.command.clone(),
+        ];
+
+        let titles = match group_by {
+            ListGroupBy::File => vec![item
+                .file
+                .as_ref()
+                .map_or("(command line)".to_string(), |file| {
+                    file.display().to_string()
+                })],
+            ListGroupBy::Tag if cmd.tags.is_empty() => vec!["(untagged)".to_string()],
+            ListGroupBy::Tag => cmd.tags.clone(),
+        };
+        for title in titles {
+            groups.entry(title).or_default().push(row.clone());
+        }
+    }
+
+    let groups: Vec<_> = groups.into_iter().collect();
+    table::render(
+        ["NAME", "DESCRIPTION", "TAGS", "WORKDIR", "COMMAND"],
+        &groups,
+    )
+}
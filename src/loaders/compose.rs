@@ -0,0 +1,275 @@
+use super::procfile::read_env_file;
+use super::{dependents, sort_by_dependencies, yaml_key_span};
+use crate::config::{Command, Config, Mode, Source};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use serde_norway::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct Compose {
+    #[serde(default)]
+    services: Mapping,
+    #[serde(flatten)]
+    unsupported: Mapping,
+}
+
+#[derive(Deserialize)]
+struct Service {
+    command: Option<ServiceCommand>,
+    #[serde(default)]
+    depends_on: DependsOn,
+    env_file: Option<EnvFiles>,
+    #[serde(default)]
+    environment: Environment,
+    working_dir: Option<PathBuf>,
+    #[serde(flatten)]
+    unsupported: Mapping,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ServiceCommand {
+    Shell(String),
+    Exec(Vec<String>),
+}
+
+/// Either a list of services, or a map of services to their start conditions.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DependsOn {
+    List(Vec<String>),
+    Map(Mapping),
+}
+
+impl Default for DependsOn {
+    fn default() -> Self {
+        Self::List(vec![])
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvFiles {
+    One(EnvFile),
+    Many(Vec<EnvFile>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EnvFile {
+    Path(PathBuf),
+    Entry {
+        path: PathBuf,
+        #[serde(default = "required")]
+        required: bool,
+    },
+}
+
+fn required() -> bool {
+    true
+}
+
+/// Either a list of KEY=VALUE, or a map of keys to values.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Environment {
+    List(Vec<String>),
+    Map(Mapping),
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::List(vec![])
+    }
+}
+
+/// Loads a docker compose file, to run its services natively: each service with a `command`
+/// becomes a run named after it, with its `environment`, `env_file` and `working_dir`. The runs
+/// are started in parallel, in the order of their `depends_on`, and the dependencies of a service
+/// are tagged with its name. The other keys cannot be honored without containers, they are
+/// returned as warnings.
+pub async fn load(path: &Path) -> anyhow::Result<(Config, Vec<String>)> {
+    let root = path
+        .parent()
+        .context("compose file has no parent directory")?;
+    let content = tokio::fs::read_to_string(path).await?;
+    let compose: Compose = serde_norway::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    let mut config = Config {
+        mode: Some(Mode::Parallel),
+        ..Default::default()
+    };
+    let mut warnings = vec![];
+    let mut warn = |message: String| warnings.push(format!("{}: {message}", path.display()));
+
+    for key in keys(&compose.unsupported) {
+        if key != "version" && key != "name" {
+            warn(format!(
+                "the top-level `{key}` key is not supported and was ignored"
+            ));
+        }
+    }
+
+    let mut services = vec![];
+    for (name, service) in compose.services {
+        let name = service_name(&name)?;
+        let service: Service = serde_norway::from_value(service)
+            .with_context(|| format!("invalid `{name}` service in {}", path.display()))?;
+        services.push((name, service));
+    }
+
+    let graph: Vec<_> = services
+        .iter()
+        .map(|(name, service)| (name.clone(), service.dependencies()))
+        .collect();
+
+    let mut runs = vec![];
+    for (name, service) in &services {
+        let Some(command) = &service.command else {
+            warn(format!(
+                "the `{name}` service has no `command` and was skipped"
+            ));
+            continue;
+        };
+        for key in keys(&service.unsupported) {
+            warn(format!(
+                "the `{key}` key of the `{name}` service is not supported and was ignored"
+            ));
+        }
+
+        let command_cmd = match command {
+            ServiceCommand::Shell(command) => shell_words::split(command)
+                .with_context(|| format!("invalid command of the `{name}` service"))?,
+            ServiceCommand::Exec(args) => args.clone(),
+        };
+
+        let command_envs = service_envs(root, service)
+            .await
+            .with_context(|| format!("invalid environment of the `{name}` service"))?;
+
+        let command_workdir = match &service.working_dir {
+            Some(dir) if dir.is_absolute() && !dir.is_dir() => {
+                warn(format!(
+                    "the `working_dir` of the `{name}` service ({}) does not exist on this machine and was ignored",
+                    dir.display()
+                ));
+                None
+            }
+            dir => dir.clone(),
+        };
+
+        let mut command_tags = vec![name.clone()];
+        command_tags.extend(dependents(&graph, name));
+
+        runs.push((
+            name.clone(),
+            Command {
+                command_cmd,
+                command_envs,
+                command_name: Some(name.clone()),
+                command_source: Some(Source {
+                    path: path.to_owned(),
+                    span: yaml_key_span(&content, "services", name),
+                }),
+                command_tags,
+                command_workdir,
+                ..Default::default()
+            },
+        ));
+    }
+
+    for name in sort_by_dependencies("service", &graph)? {
+        if let Some(index) = runs.iter().position(|(run, _)| *run == name) {
+            config.runs.push(runs.remove(index).1);
+        }
+    }
+    Ok((config, warnings))
+}
+
+/// The names of the services of a docker compose file, in the order of the file, whether they
+/// have a `command` or not.
+pub async fn services(path: &Path) -> anyhow::Result<Vec<String>> {
+    let content = tokio::fs::read_to_string(path).await?;
+    let compose: Compose = serde_norway::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+    compose.services.keys().map(service_name).collect()
+}
+
+fn service_name(name: &Value) -> anyhow::Result<String> {
+    Ok(name
+        .as_str()
+        .context("service names must be strings")?
+        .to_string())
+}
+
+/// The variables of the env files, then of the environment which takes precedence.
+async fn service_envs(root: &Path, service: &Service) -> anyhow::Result<Vec<String>> {
+    let mut envs = vec![];
+    for env_file in service.env_files() {
+        let (path, required) = match env_file {
+            EnvFile::Path(path) => (root.join(path), true),
+            EnvFile::Entry { path, required } => (root.join(path), *required),
+        };
+        match read_env_file(&path).await? {
+            Some(env_file) => envs.extend(env_file),
+            None if required => bail!("env file {} not found", path.display()),
+            None => {}
+        }
+    }
+    envs.extend(service.environment.to_envs());
+    Ok(envs)
+}
+
+impl Service {
+    fn env_files(&self) -> &[EnvFile] {
+        match &self.env_file {
+            Some(EnvFiles::One(env_file)) => std::slice::from_ref(env_file),
+            Some(EnvFiles::Many(env_files)) => env_files,
+            None => &[],
+        }
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        match &self.depends_on {
+            DependsOn::List(services) => services.clone(),
+            DependsOn::Map(services) => keys(services).map(ToString::to_string).collect(),
+        }
+    }
+}
+
+impl Environment {
+    /// Variables without a value are passed through from the host, which is already the case
+    /// when running natively.
+    fn to_envs(&self) -> Vec<String> {
+        match self {
+            Self::List(envs) => envs
+                .iter()
+                .filter(|env| env.contains('='))
+                .cloned()
+                .collect(),
+            Self::Map(envs) => envs
+                .iter()
+                .filter_map(|(key, value)| {
+                    let value = match value {
+                        Value::String(value) => value.clone(),
+                        Value::Number(value) => value.to_string(),
+                        Value::Bool(value) => value.to_string(),
+                        _ => return None,
+                    };
+                    Some(format!("{}={value}", key.as_str()?))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the string keys of a mapping, without the `x-` extensions which are meant to be
+/// ignored.
+fn keys(mapping: &Mapping) -> impl Iterator<Item = &str> {
+    mapping
+        .keys()
+        .filter_map(Value::as_str)
+        .filter(|key| !key.starts_with("x-"))
+}
@@ -0,0 +1,242 @@
+use super::procfile::read_env_file;
+use super::{dependents, lines, shell_command, sort_by_dependencies};
+use crate::config::{Command, Config, Source};
+use crate::diagnostic;
+use anyhow::bail;
+use std::ops::Range;
+use std::path::Path;
+
+struct Recipe {
+    name: String,
+    description: Option<String>,
+    dependencies: Vec<String>,
+    lines: Vec<String>,
+    span: Range<usize>,
+}
+
+/// The settings of a justfile which can be translated.
+#[derive(Default)]
+struct Settings {
+    dotenv_load: bool,
+    export: bool,
+}
+
+/// Loads the recipes of a simple justfile. Each recipe becomes a run named after it, with the
+/// comment right above it as description, executing its lines with `sh` and stopping at the first
+/// failure. The dependencies of a recipe are tagged with its name, so that `--tags build` runs the
+/// `build` recipe after its dependencies. Exported variables are set for all runs, and
+/// `set dotenv-load` loads the sibling `.env` file.
+///
+/// Only string literals are supported: parameters, interpolations, expressions, attributes,
+/// shebang recipes and the other settings are rejected with an error pointing at them.
+pub async fn load(path: &Path) -> anyhow::Result<Config> {
+    let justfile = tokio::fs::read_to_string(path).await?;
+
+    let mut settings = Settings::default();
+    let mut recipes: Vec<Recipe> = vec![];
+    let mut exports = vec![];
+    let mut variables = vec![];
+    let mut comment: Option<String> = None;
+
+    for (span, line) in lines(&justfile) {
+        let error = |message: &str| {
+            anyhow::anyhow!(diagnostic::render(message, path, &justfile, span.clone()))
+        };
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            comment = None;
+        } else if line.starts_with([' ', '\t']) {
+            let recipe = recipes
+                .last_mut()
+                .ok_or_else(|| error("unexpected indentation"))?;
+            let line = parse_line(trimmed, recipe.lines.is_empty()).map_err(error)?;
+            recipe.lines.push(line);
+        } else if let Some(text) = trimmed.strip_prefix('#') {
+            comment = Some(text.trim().to_string());
+        } else if let Some(setting) = trimmed.strip_prefix("set ") {
+            parse_setting(setting, &mut settings).map_err(|message| error(&message))?;
+        } else if let Some((name, value)) = trimmed.split_once(":=") {
+            let (export, env) = parse_assignment(name, value).map_err(error)?;
+            if export {
+                exports.push(env);
+            } else {
+                variables.push(env);
+            }
+        } else {
+            let (name, dependencies) = parse_recipe(trimmed).map_err(error)?;
+            let start = span.start + line.find(name).unwrap_or_default();
+            recipes.push(Recipe {
+                name: name.to_string(),
+                description: comment.take(),
+                dependencies,
+                lines: vec![],
+                span: start..start + name.len(),
+            });
+        }
+    }
+
+    if recipes.is_empty() {
+        bail!("no recipes found in {}", path.display());
+    }
+
+    let mut config = Config::default();
+    if settings.dotenv_load {
+        // as with just, a missing .env file is ignored
+        config.envs = read_env_file(&path.with_file_name(".env"))
+            .await?
+            .unwrap_or_default();
+    }
+    if settings.export {
+        config.envs.extend(variables);
+    }
+    config.envs.extend(exports);
+
+    let graph: Vec<_> = recipes
+        .iter()
+        .map(|recipe| (recipe.name.clone(), recipe.dependencies.clone()))
+        .collect();
+    for name in sort_by_dependencies("recipe", &graph)? {
+        let Some(recipe) = recipes.iter().find(|recipe| recipe.name == name) else {
+            continue;
+        };
+        // recipes without lines only group their dependencies
+        if recipe.lines.is_empty() {
+            continue;
+        }
+
+        let mut command_tags = vec![name.clone()];
+        command_tags.extend(dependents(&graph, &name));
+
+        config.runs.push(Command {
+            command_cmd: shell_command(&recipe.lines),
+            command_description: recipe.description.clone(),
+            command_name: Some(name),
+            command_source: Some(Source {
+                path: path.to_owned(),
+                span: recipe.span.clone(),
+            }),
+            command_tags,
+            ..Default::default()
+        });
+    }
+
+    Ok(config)
+}
+
+fn parse_line(line: &str, first: bool) -> Result<String, &'static str> {
+    if first && line.starts_with("#!") {
+        return Err("shebang recipes are not supported");
+    }
+    if line.contains("{{") {
+        return Err("interpolations are not supported");
+    }
+    if line.starts_with('-') {
+        return Err("ignoring the errors with `-` is not supported");
+    }
+    // `@` only silences the echo of the line
+    Ok(line.trim_start_matches('@').to_string())
+}
+
+fn parse_setting(setting: &str, settings: &mut Settings) -> Result<(), String> {
+    let (name, value) = match setting.split_once(":=") {
+        Some((name, value)) => (name.trim(), value.trim()),
+        None => (setting.trim(), "true"),
+    };
+    let setting = match name {
+        "dotenv-load" => &mut settings.dotenv_load,
+        "export" => &mut settings.export,
+        _ => return Err(format!("the `{name}` setting is not supported")),
+    };
+    *setting = match value {
+        "true" => true,
+        "false" => false,
+        _ => {
+            return Err(format!(
+                "the `{name}` setting only supports `true` and `false`"
+            ))
+        }
+    };
+    Ok(())
+}
+
+/// Returns whether the variable is exported, and the variable as KEY=VALUE.
+fn parse_assignment(name: &str, value: &str) -> Result<(bool, String), &'static str> {
+    let name = name.trim();
+    if name.starts_with("alias ") {
+        return Err("aliases are not supported");
+    }
+    let (export, name) = match name.strip_prefix("export ") {
+        Some(name) => (true, name.trim()),
+        None => (false, name),
+    };
+    let value =
+        string_literal(value.trim()).ok_or("only string literals are supported in assignments")?;
+    Ok((export, format!("{name}={value}")))
+}
+
+/// Returns the name and the dependencies of a recipe.
+fn parse_recipe(line: &str) -> Result<(&str, Vec<String>), &'static str> {
+    if line.starts_with('[') {
+        return Err("recipe attributes are not supported");
+    }
+    if line.starts_with("import ") || line.starts_with("mod ") {
+        return Err("imports and modules are not supported");
+    }
+
+    let (header, dependencies) = line
+        .split_once(':')
+        .ok_or("expected a recipe, a setting or an assignment")?;
+    // `@` only silences the echo of the lines
+    let mut header = header.trim_start_matches('@').split_whitespace();
+    let name = header.next().unwrap_or_default();
+    if !is_identifier(name) {
+        return Err("invalid recipe name");
+    }
+    if header.next().is_some() {
+        return Err("recipe parameters are not supported");
+    }
+
+    let dependencies = dependencies
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|dependency| {
+            Some(dependency.to_string())
+                .filter(|dependency| is_identifier(dependency))
+                .ok_or("only dependencies without arguments are supported")
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((name, dependencies))
+}
+
+/// Parses a quoted string, with the escape sequences of double quoted strings.
+fn string_literal(value: &str) -> Option<String> {
+    if let Some(quoted) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Some(quoted.to_string()).filter(|quoted| !quoted.contains('\''));
+    }
+    let quoted = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut string = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return None,
+            '\\' => string.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                c @ ('"' | '\\') => c,
+                _ => return None,
+            }),
+            c => string.push(c),
+        }
+    }
+    Some(string)
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
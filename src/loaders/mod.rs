@@ -0,0 +1,194 @@
+pub mod compose;
+mod justfile;
+mod package_json;
+mod procfile;
+mod taskfile;
+
+use crate::config::Config;
+use anyhow::bail;
+use std::ops::Range;
+use std::path::Path;
+
+/// The config files of other tools, which can be loaded as a config.
+#[derive(Debug, Clone, Copy)]
+pub enum Loader {
+    Compose,
+    Justfile,
+    PackageJson,
+    Procfile,
+    Taskfile,
+}
+
+impl Loader {
+    /// Detects the loader from the file name (e.g. "Procfile" or "Procfile.dev").
+    pub fn detect<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let file_name = path.as_ref().file_name()?.to_str()?;
+        if matches!(
+            file_name,
+            "compose.yaml" | "compose.yml" | "docker-compose.yaml" | "docker-compose.yml"
+        ) {
+            return Some(Self::Compose);
+        }
+        if matches!(file_name, "justfile" | "Justfile" | ".justfile") {
+            return Some(Self::Justfile);
+        }
+        if file_name == "package.json" {
+            return Some(Self::PackageJson);
+        }
+        if file_name == "Procfile" || file_name.starts_with("Procfile.") {
+            return Some(Self::Procfile);
+        }
+        if matches!(
+            file_name,
+            "Taskfile.yml" | "Taskfile.yaml" | "taskfile.yml" | "taskfile.yaml"
+        ) {
+            return Some(Self::Taskfile);
+        }
+        None
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Compose => "compose file",
+            Self::Justfile => "justfile",
+            Self::PackageJson => "package.json",
+            Self::Procfile => "Procfile",
+            Self::Taskfile => "Taskfile",
+        }
+    }
+
+    /// Loads the config file, with the warnings about what could not be translated (e.g. the
+    /// keys of a compose file which cannot be honored without containers).
+    pub async fn load(self, path: &Path) -> anyhow::Result<(Config, Vec<String>)> {
+        match self {
+            Self::Compose => compose::load(path).await,
+            Self::Justfile => Ok((justfile::load(path).await?, vec![])),
+            Self::PackageJson => Ok((package_json::load(path).await?, vec![])),
+            Self::Procfile => Ok((procfile::load(path).await?, vec![])),
+            Self::Taskfile => Ok((taskfile::load(path).await?, vec![])),
+        }
+    }
+}
+
+/// Orders the names of a dependency graph (e.g. services and their `depends_on`) so that each one
+/// comes after its dependencies, keeping the order of the file otherwise. `kind` names the nodes in
+/// the error messages.
+fn sort_by_dependencies(
+    kind: &str,
+    graph: &[(String, Vec<String>)],
+) -> anyhow::Result<Vec<String>> {
+    fn visit(
+        kind: &str,
+        name: &str,
+        graph: &[(String, Vec<String>)],
+        path: &mut Vec<String>,
+        order: &mut Vec<String>,
+    ) -> anyhow::Result<()> {
+        if order.iter().any(|visited| visited == name) {
+            return Ok(());
+        }
+        if path.iter().any(|visiting| visiting == name) {
+            bail!(
+                "circular dependency between the {kind}s: {} -> {name}",
+                path.join(" -> ")
+            );
+        }
+
+        path.push(name.to_string());
+        for dependency in dependencies(graph, name) {
+            if !graph.iter().any(|(node, _)| node == dependency) {
+                bail!("the `{name}` {kind} depends on the unknown `{dependency}` {kind}");
+            }
+            visit(kind, dependency, graph, path, order)?;
+        }
+        path.pop();
+        order.push(name.to_string());
+        Ok(())
+    }
+
+    let mut order = vec![];
+    for (name, _) in graph {
+        visit(kind, name, graph, &mut vec![], &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Returns the names of the nodes depending on `name`, directly or not. They are used as extra tags,
+/// so that filtering on a task also selects its dependencies.
+fn dependents(graph: &[(String, Vec<String>)], name: &str) -> Vec<String> {
+    let mut dependents: Vec<String> = vec![];
+    let mut queue = vec![name];
+    while let Some(current) = queue.pop() {
+        for (node, _) in graph
+            .iter()
+            .filter(|(_, deps)| deps.iter().any(|d| d == current))
+        {
+            if !dependents.contains(node) {
+                dependents.push(node.clone());
+                queue.push(node);
+            }
+        }
+    }
+    // in the order of the file
+    graph
+        .iter()
+        .map(|(node, _)| node)
+        .filter(|node| dependents.contains(node))
+        .cloned()
+        .collect()
+}
+
+/// Returns the span and the content of the lines, without their line endings.
+fn lines(source: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    source.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        let line = line.trim_end_matches(['\n', '\r']);
+        Some((start..start + line.len(), line))
+    })
+}
+
+/// Runs shell lines with `sh`, stopping at the first failure when there are several of them.
+fn shell_command(lines: &[String]) -> Vec<String> {
+    let flags = if lines.len() > 1 { "-ec" } else { "-c" };
+    vec!["sh".into(), flags.into(), lines.join("\n")]
+}
+
+fn dependencies<'a>(graph: &'a [(String, Vec<String>)], name: &str) -> &'a [String] {
+    graph
+        .iter()
+        .find(|(node, _)| node == name)
+        .map_or(&[], |(_, deps)| deps.as_slice())
+}
+
+/// Locates a key nested under a top-level section of a YAML file (e.g. a service under
+/// `services:`), for error messages. Only the keys at the indentation of the first entry of the
+/// section are matched, not the ones nested in the entries.
+fn yaml_key_span(content: &str, section: &str, key: &str) -> Range<usize> {
+    let mut in_section = false;
+    let mut entry_indent = None;
+    for (span, line) in lines(content) {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if indent == 0 {
+            in_section = trimmed
+                .strip_prefix(section)
+                .is_some_and(|rest| rest.starts_with(':'));
+            entry_indent = None;
+        } else if in_section && *entry_indent.get_or_insert(indent) == indent {
+            let quoted = [
+                format!("{key}:"),
+                format!("\"{key}\":"),
+                format!("'{key}':"),
+            ];
+            if let Some(prefix) = quoted.iter().find(|prefix| trimmed.starts_with(*prefix)) {
+                let start = span.start + indent;
+                return start..start + prefix.len() - 1;
+            }
+        }
+    }
+    0..0
+}
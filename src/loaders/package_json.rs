@@ -0,0 +1,150 @@
+use crate::config::{Command, Config, Source};
+use anyhow::Context;
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct Package {
+    name: Option<String>,
+    /// The scripts are run in the order they are declared in
+    #[serde(default)]
+    scripts: IndexMap<String, String>,
+    workspaces: Option<Workspaces>,
+}
+
+/// npm and yarn accept both a list of globs and an object (yarn v1 `nohoist`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Workspaces {
+    Globs(Vec<String>),
+    Object { packages: Vec<String> },
+}
+
+#[derive(Deserialize)]
+struct PnpmWorkspace {
+    #[serde(default)]
+    packages: Vec<String>,
+}
+
+/// Loads the scripts of a package.json, and of the packages of its workspaces (npm, yarn or pnpm).
+/// Each script runs with `sh -c` in its package directory, with the `node_modules/.bin` of the
+/// package and of the root on the PATH, and is tagged with its name and the name of its package.
+pub async fn load(path: &Path) -> anyhow::Result<Config> {
+    let root = path
+        .parent()
+        .context("package.json has no parent directory")?;
+    let package = read_package(path).await?;
+
+    let mut globs = match &package.workspaces {
+        Some(Workspaces::Globs(globs) | Workspaces::Object { packages: globs }) => globs.clone(),
+        None => vec![],
+    };
+    let pnpm_workspace = root.join("pnpm-workspace.yaml");
+    if pnpm_workspace.is_file() {
+        let pnpm: PnpmWorkspace =
+            serde_norway::from_str(&tokio::fs::read_to_string(&pnpm_workspace).await?)
+                .with_context(|| format!("failed to parse {}", pnpm_workspace.display()))?;
+        globs.extend(pnpm.packages);
+    }
+
+    let mut packages = vec![(path.to_owned(), package)];
+    for dir in expand_workspaces(root, &globs)? {
+        let path = dir.join("package.json");
+        if path.is_file() {
+            let package = read_package(&path).await?;
+            packages.push((path, package));
+        }
+    }
+
+    let single = packages.len() == 1;
+    let mut config = Config::default();
+    for (path, package) in packages {
+        let dir = path.parent().unwrap_or(root);
+        let package_name = package.name.clone().unwrap_or_else(|| {
+            dir.file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        });
+        let content = tokio::fs::read_to_string(&path).await?;
+        let workdir = dir
+            .strip_prefix(root)
+            .ok()
+            .filter(|relative| !relative.as_os_str().is_empty())
+            .map(Path::to_owned);
+        let bin_path = bin_path(workdir.as_deref());
+
+        for (script_name, script) in package.scripts {
+            config.runs.push(Command {
+                command_cmd: vec!["sh".into(), "-c".into(), script],
+                command_path: bin_path.clone(),
+                command_name: Some(if single {
+                    script_name.clone()
+                } else {
+                    format!("{package_name}:{script_name}")
+                }),
+                command_source: Some(Source {
+                    path: path.clone(),
+                    span: script_span(&content, &script_name),
+                }),
+                command_tags: vec![package_name.clone(), script_name],
+                command_workdir: workdir.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(config)
+}
+
+async fn read_package(path: &Path) -> anyhow::Result<Package> {
+    let content = tokio::fs::read_to_string(path).await?;
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Returns the directories matching the workspace globs, in order. Globs starting with `!`
+/// exclude directories (pnpm).
+fn expand_workspaces(root: &Path, globs: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut dirs: Vec<PathBuf> = vec![];
+    let mut excluded: Vec<PathBuf> = vec![];
+
+    for pattern in globs {
+        let (pattern, list) = match pattern.strip_prefix('!') {
+            Some(pattern) => (root.join(pattern), &mut excluded),
+            None => (root.join(pattern), &mut dirs),
+        };
+        let mut matches = glob::glob(&pattern.to_string_lossy())
+            .with_context(|| format!("invalid workspace glob {}", pattern.display()))?
+            .collect::<Result<Vec<_>, _>>()?;
+        matches.sort();
+        for dir in matches.into_iter().filter(|dir| dir.is_dir()) {
+            let dir = dir.canonicalize()?;
+            if !list.contains(&dir) {
+                list.push(dir);
+            }
+        }
+    }
+
+    dirs.retain(|dir| !excluded.contains(dir));
+    Ok(dirs)
+}
+
+/// The `node_modules/.bin` directories of the package and of the root, relative to the directory
+/// of the package, which is relative to the root.
+fn bin_path(workdir: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("node_modules/.bin")];
+    if let Some(workdir) = workdir {
+        let mut root: PathBuf = workdir.components().map(|_| "..").collect();
+        root.push("node_modules/.bin");
+        paths.push(root);
+    }
+    paths
+}
+
+/// Locates the key of a script in the package.json, for error messages.
+fn script_span(content: &str, script_name: &str) -> std::ops::Range<usize> {
+    let key = format!("\"{script_name}\"");
+    content
+        .find("\"scripts\"")
+        .and_then(|scripts| Some(scripts + content[scripts..].find(&key)?))
+        .map_or(0..0, |start| start..start + key.len())
+}
@@ -0,0 +1,103 @@
+use super::lines;
+use crate::config::{Command, Config, Mode, Source};
+use crate::diagnostic;
+use anyhow::Context;
+use std::path::Path;
+
+/// Loads a foreman-style Procfile: each `name: command` line is a run executed with `sh -c`, all
+/// of them in parallel by default. The variables of a sibling `.env` file are set for all runs.
+pub async fn load(path: &Path) -> anyhow::Result<Config> {
+    let procfile = tokio::fs::read_to_string(path).await?;
+
+    let mut config = Config {
+        mode: Some(Mode::Parallel),
+        ..Default::default()
+    };
+
+    for (span, line) in entries(&procfile) {
+        let Some((name, command)) = line
+            .split_once(':')
+            .filter(|(name, _)| is_process_name(name))
+        else {
+            anyhow::bail!(diagnostic::render(
+                "invalid Procfile line, expected `name: command`",
+                path,
+                &procfile,
+                span,
+            ));
+        };
+
+        config.runs.push(Command {
+            command_cmd: vec!["sh".into(), "-c".into(), command.trim().into()],
+            command_name: Some(name.into()),
+            command_source: Some(Source {
+                path: path.to_owned(),
+                span,
+            }),
+            ..Default::default()
+        });
+    }
+
+    if let Some(envs) = read_env_file(&path.with_file_name(".env")).await? {
+        config.envs = envs;
+    }
+
+    Ok(config)
+}
+
+/// Reads and parses a `.env` file, if it exists.
+pub(super) async fn read_env_file(path: &Path) -> anyhow::Result<Option<Vec<String>>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(env) => Ok(Some(parse_env_file(path, &env)?)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+/// Parses a `.env` file into KEY=VALUE pairs. Values may be quoted, and lines may start with
+/// `export` as in shell scripts.
+fn parse_env_file(path: &Path, env: &str) -> anyhow::Result<Vec<String>> {
+    entries(env)
+        .map(|(span, line)| {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let Some((key, value)) = line.split_once('=') else {
+                anyhow::bail!(diagnostic::render(
+                    "invalid environment variable, expected `KEY=VALUE`",
+                    path,
+                    env,
+                    span,
+                ));
+            };
+
+            let value = value.trim();
+            let value = if let Some(quoted) = strip_quotes(value, '\'') {
+                quoted.to_string()
+            } else if let Some(quoted) = strip_quotes(value, '"') {
+                quoted.replace("\\n", "\n").replace("\\\"", "\"")
+            } else {
+                value.to_string()
+            };
+
+            Ok(format!("{}={}", key.trim(), value))
+        })
+        .collect()
+}
+
+/// Returns the span and the content of the lines which are neither empty nor comments.
+fn entries(source: &str) -> impl Iterator<Item = (std::ops::Range<usize>, &str)> {
+    lines(source).filter(|(_, line)| {
+        let line = line.trim_start();
+        !line.is_empty() && !line.starts_with('#')
+    })
+}
+
+fn strip_quotes(value: &str, quote: char) -> Option<&str> {
+    value.strip_prefix(quote)?.strip_suffix(quote)
+}
+
+fn is_process_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
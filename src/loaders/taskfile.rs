@@ -0,0 +1,227 @@
+use super::procfile::read_env_file;
+use super::{dependents, shell_command, sort_by_dependencies, yaml_key_span};
+use crate::config::{Command, Config, Source};
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use serde_norway::{Mapping, Value};
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct Taskfile {
+    version: Option<Value>,
+    #[serde(default)]
+    dotenv: Vec<PathBuf>,
+    #[serde(default)]
+    env: Mapping,
+    #[serde(default)]
+    tasks: Mapping,
+    #[serde(flatten)]
+    unsupported: Mapping,
+}
+
+/// A task can also be declared as a single command, or as a list of commands.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskDefinition {
+    Command(String),
+    Commands(Vec<TaskCommand>),
+    Task(Box<Task>),
+}
+
+#[derive(Deserialize, Default)]
+struct Task {
+    cmd: Option<TaskCommand>,
+    #[serde(default)]
+    cmds: Vec<TaskCommand>,
+    #[serde(default)]
+    deps: Vec<TaskCommand>,
+    desc: Option<String>,
+    dir: Option<PathBuf>,
+    #[serde(default)]
+    env: Mapping,
+    summary: Option<String>,
+    #[serde(flatten)]
+    unsupported: Mapping,
+}
+
+/// Commands and dependencies are either strings, or maps with a `cmd` or a `task` key.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskCommand {
+    String(String),
+    Map(Mapping),
+}
+
+/// Loads the tasks of a go-task Taskfile (version 3). Each task becomes a run named after it, with
+/// its description, `dir` and `env`, executing its commands with `sh` and stopping at the first
+/// failure. The dependencies of a task are tagged with its name, so that `--tags build` runs the
+/// `build` task after its dependencies. Templates, variables, includes and the other features
+/// which cannot be translated are rejected.
+pub async fn load(path: &Path) -> anyhow::Result<Config> {
+    let root = path.parent().context("Taskfile has no parent directory")?;
+    let content = tokio::fs::read_to_string(path).await?;
+    let taskfile: Taskfile = serde_norway::from_str(&content)
+        .with_context(|| format!("failed to parse {}", path.display()))?;
+
+    if let Some(version) = &taskfile.version {
+        let version = scalar(version).unwrap_or_default();
+        if !version.starts_with('3') {
+            bail!("only version 3 of Taskfiles is supported, found `{version}`");
+        }
+    }
+    if let Some(key) = taskfile.unsupported.keys().find_map(Value::as_str) {
+        bail!("the top-level `{key}` key is not supported");
+    }
+
+    let mut config = Config::default();
+    for dotenv in &taskfile.dotenv {
+        // as with go-task, missing dotenv files are ignored
+        if let Some(envs) = read_env_file(&root.join(dotenv)).await? {
+            config.envs.extend(envs);
+        }
+    }
+    config
+        .envs
+        .extend(envs(&taskfile.env).context("invalid top-level `env`")?);
+
+    let tasks = taskfile
+        .tasks
+        .into_iter()
+        .map(|(name, task)| {
+            let name = name.as_str().context("task names must be strings")?;
+            Ok((name.to_string(), read_task(name, task)?))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let graph = tasks
+        .iter()
+        .map(|(name, task)| {
+            let deps = task
+                .deps
+                .iter()
+                .map(|dep| entry(dep, "task", &["task"]))
+                .collect::<anyhow::Result<_>>()
+                .with_context(|| format!("invalid `deps` of the `{name}` task"))?;
+            Ok((name.clone(), deps))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let order = sort_by_dependencies("task", &graph)?;
+
+    for name in order {
+        let Some((_, task)) = tasks.iter().find(|(task, _)| *task == name) else {
+            continue;
+        };
+
+        let lines = task
+            .cmd
+            .iter()
+            .chain(&task.cmds)
+            .map(|cmd| entry(cmd, "cmd", &["cmd", "silent"]))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("invalid commands of the `{name}` task"))?;
+        // tasks without commands only group their dependencies
+        if lines.is_empty() {
+            continue;
+        }
+        for text in lines
+            .iter()
+            .map(String::as_str)
+            .chain(task.dir.iter().filter_map(|dir| dir.to_str()))
+        {
+            if text.contains("{{") {
+                bail!("templates are not supported, found in the `{name}` task: {text}");
+            }
+        }
+
+        let mut command_tags = vec![name.clone()];
+        command_tags.extend(dependents(&graph, &name));
+
+        config.runs.push(Command {
+            command_cmd: shell_command(&lines),
+            command_description: task.desc.clone().or_else(|| task.summary.clone()),
+            command_envs: envs(&task.env)
+                .with_context(|| format!("invalid `env` of the `{name}` task"))?,
+            command_name: Some(name.clone()),
+            command_source: Some(Source {
+                path: path.to_owned(),
+                span: yaml_key_span(&content, "tasks", &name),
+            }),
+            command_tags,
+            command_workdir: task.dir.clone(),
+            ..Default::default()
+        });
+    }
+
+    Ok(config)
+}
+
+fn read_task(name: &str, task: Value) -> anyhow::Result<Task> {
+    let task =
+        match serde_norway::from_value(task).with_context(|| format!("invalid `{name}` task"))? {
+            TaskDefinition::Command(cmd) => Task {
+                cmd: Some(TaskCommand::String(cmd)),
+                ..Default::default()
+            },
+            TaskDefinition::Commands(cmds) => Task {
+                cmds,
+                ..Default::default()
+            },
+            TaskDefinition::Task(task) => *task,
+        };
+    if let Some(key) = task
+        .unsupported
+        .keys()
+        .filter_map(Value::as_str)
+        // only hides the commands in the output of go-task
+        .find(|key| *key != "silent")
+    {
+        bail!("the `{key}` key of the `{name}` task is not supported");
+    }
+    Ok(task)
+}
+
+/// Returns the value of a string, or of the `key` of a map whose keys are all `allowed`.
+fn entry(entry: &TaskCommand, key: &str, allowed: &[&str]) -> anyhow::Result<String> {
+    let map = match entry {
+        TaskCommand::String(value) => return Ok(value.clone()),
+        TaskCommand::Map(map) => map,
+    };
+    if let Some(other) = map
+        .keys()
+        .filter_map(Value::as_str)
+        .find(|other| !allowed.contains(other))
+    {
+        if other == "task" {
+            bail!("calling a task from the commands is not supported, use `deps` instead");
+        }
+        bail!("the `{other}` key is not supported");
+    }
+    map.get(key)
+        .and_then(Value::as_str)
+        .map(ToString::to_string)
+        .with_context(|| format!("expected a string or a map with a `{key}` key"))
+}
+
+fn envs(env: &Mapping) -> anyhow::Result<Vec<String>> {
+    env.iter()
+        .map(|(key, value)| {
+            let key = key.as_str().context("variable names must be strings")?;
+            let Some(value) = scalar(value) else {
+                bail!("dynamic variables are not supported, found in `{key}`");
+            };
+            if value.contains("{{") {
+                bail!("templates are not supported, found in `{key}`: {value}");
+            }
+            Ok(format!("{key}={value}"))
+        })
+        .collect()
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,404 @@
+use crate::check::{self, Severity};
+use crate::config::Config;
+use crate::schema::{config_keys, ConfigKey};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{Completion, HoverRequest, Request as _};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, Diagnostic,
+    DiagnosticSeverity, Documentation, Hover, HoverContents, HoverParams, HoverProviderCapability,
+    MarkupContent, MarkupKind, Position, PublishDiagnosticsParams, Range, ServerCapabilities,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Runs a language server over stdio, for the config files. Diagnostics are reported for all the
+/// formats, by checking the config as `run --check` would. The completion of the keys and of
+/// their values, and the documentation on hover, are provided for TOML.
+pub fn run() -> anyhow::Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["[".into(), ".".into(), "=".into()]),
+            ..Default::default()
+        }),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    serve(&connection)?;
+
+    // the writer thread stops once the connection is dropped
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+fn serve(connection: &Connection) -> anyhow::Result<()> {
+    let keys = config_keys();
+    let mut documents: HashMap<Url, String> = HashMap::new();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    break;
+                }
+                let response = handle_request(request, &documents, &keys);
+                connection.sender.send(Message::Response(response))?;
+            }
+            Message::Notification(notification) => {
+                let method = notification.method.clone();
+                let uri = match handle_notification(notification, &mut documents) {
+                    Ok(Some(uri)) => uri,
+                    Ok(None) => continue,
+                    // a malformed notification must not stop the server, it is only logged
+                    Err(err) => {
+                        eprintln!("run lsp: ignored invalid {method} notification: {err:#}");
+                        continue;
+                    }
+                };
+                let diagnostics = match (documents.get(&uri), uri.to_file_path()) {
+                    (Some(text), Ok(path)) => diagnostics(&path, text),
+                    _ => vec![],
+                };
+                let params = PublishDiagnosticsParams {
+                    uri,
+                    diagnostics,
+                    version: None,
+                };
+                connection
+                    .sender
+                    .send(Message::Notification(Notification::new(
+                        PublishDiagnostics::METHOD.into(),
+                        params,
+                    )))?;
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn handle_request(
+    request: Request,
+    documents: &HashMap<Url, String>,
+    keys: &[ConfigKey],
+) -> Response {
+    let result = match request.method.as_str() {
+        Completion::METHOD => {
+            serde_json::from_value::<CompletionParams>(request.params).map(|params| {
+                let position = params.text_document_position;
+                let items = documents
+                    .get(&position.text_document.uri)
+                    .filter(|_| is_toml(&position.text_document.uri))
+                    .map(|text| completions(text, position.position, keys))
+                    .unwrap_or_default();
+                serde_json::to_value(items)
+            })
+        }
+        HoverRequest::METHOD => {
+            serde_json::from_value::<HoverParams>(request.params).map(|params| {
+                let position = params.text_document_position_params;
+                let hover = documents
+                    .get(&position.text_document.uri)
+                    .filter(|_| is_toml(&position.text_document.uri))
+                    .and_then(|text| hover(text, position.position, keys));
+                serde_json::to_value(hover)
+            })
+        }
+        method => {
+            return Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported method {method}"),
+            )
+        }
+    };
+
+    match result {
+        Ok(Ok(result)) => Response::new_ok(request.id, result),
+        Ok(Err(err)) | Err(err) => {
+            Response::new_err(request.id, ErrorCode::InvalidParams as i32, err.to_string())
+        }
+    }
+}
+
+/// Keeps track of the content of the documents, and returns the document to check if any.
+fn handle_notification(
+    notification: Notification,
+    documents: &mut HashMap<Url, String>,
+) -> anyhow::Result<Option<Url>> {
+    match notification.method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: lsp_types::DidOpenTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            documents.insert(uri.clone(), params.text_document.text);
+            Ok(Some(uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: lsp_types::DidChangeTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            let uri = params.text_document.uri;
+            // the documents are synchronized in full
+            if let Some(change) = params.content_changes.into_iter().last() {
+                documents.insert(uri.clone(), change.text);
+            }
+            Ok(Some(uri))
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: lsp_types::DidCloseTextDocumentParams =
+                serde_json::from_value(notification.params)?;
+            documents.remove(&params.text_document.uri);
+            Ok(Some(params.text_document.uri))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Parses the config, and reports all the problems `run --check` would find, or the error which
+/// prevents it from being parsed.
+fn diagnostics(path: &Path, text: &str) -> Vec<Diagnostic> {
+    let problems = match Config::parse_str(path, text) {
+        Err(err) => vec![(DiagnosticSeverity::ERROR, err.message, err.span)],
+        Ok(mut config) => match config.set_absolute_workdir(path) {
+            Err(err) => vec![(DiagnosticSeverity::ERROR, format!("{err:#}"), None)],
+            Ok(()) => check::problems(config)
+                .into_iter()
+                .map(|problem| {
+                    let severity = match problem.severity {
+                        Severity::Error => DiagnosticSeverity::ERROR,
+                        Severity::Warning => DiagnosticSeverity::WARNING,
+                    };
+                    // the problems located in another file are reported at the top
+                    let span = problem
+                        .source
+                        .filter(|source| source.path == path)
+                        .map(|source| source.span);
+                    (severity, problem.message, span)
+                })
+                .collect(),
+        },
+    };
+
+    problems
+        .into_iter()
+        .map(|(severity, message, span)| {
+            let span = span.unwrap_or(0..0);
+            Diagnostic {
+                range: Range::new(position_of(text, span.start), position_of(text, span.end)),
+                severity: Some(severity),
+                source: Some("run".into()),
+                message,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// Completes the keys of the current table, or the values of the key of the current line.
+fn completions(text: &str, position: Position, keys: &[ConfigKey]) -> Vec<CompletionItem> {
+    let (table, line) = context(text, position);
+    let line = &line[..offset_in_line(line, position.character)];
+
+    if let Some((key, _)) = line.split_once('=') {
+        let key = join(&table, key.trim());
+        return keys
+            .iter()
+            .filter(|config_key| config_key.key == key)
+            .flat_map(|config_key| {
+                let values: Vec<String> = match config_key.kind.as_str() {
+                    "boolean" => vec!["true".into(), "false".into()],
+                    kind if kind.contains('|') => kind
+                        .split('|')
+                        .map(|value| format!("\"{value}\""))
+                        .collect(),
+                    _ => vec![],
+                };
+                values.into_iter().map(|value| CompletionItem {
+                    label: value,
+                    kind: Some(CompletionItemKind::VALUE),
+                    ..Default::default()
+                })
+            })
+            .collect();
+    }
+
+    // within a table header, the tables are completed from the root
+    let table = if line.trim_start().starts_with('[') {
+        String::new()
+    } else {
+        table
+    };
+    let prefix = if table.is_empty() {
+        String::new()
+    } else {
+        format!("{table}.")
+    };
+
+    let mut items: Vec<CompletionItem> = vec![];
+    for config_key in keys {
+        let Some(rest) = config_key.key.strip_prefix(&prefix) else {
+            continue;
+        };
+        let (name, is_table) = match rest.split_once('.') {
+            Some((name, _)) => (name.trim_end_matches("[]"), true),
+            None => (rest, config_key.kind == "table"),
+        };
+        if name.starts_with('<') || items.iter().any(|item| item.label == name) {
+            continue;
+        }
+        items.push(CompletionItem {
+            label: name.into(),
+            kind: Some(if is_table {
+                CompletionItemKind::MODULE
+            } else {
+                CompletionItemKind::PROPERTY
+            }),
+            detail: Some(if is_table {
+                "table".into()
+            } else {
+                config_key.kind.clone()
+            }),
+            documentation: config_key
+                .description
+                .clone()
+                .filter(|_| !is_table)
+                .map(Documentation::String),
+            ..Default::default()
+        });
+    }
+    items
+}
+
+/// Documents the key under the cursor.
+fn hover(text: &str, position: Position, keys: &[ConfigKey]) -> Option<Hover> {
+    let (table, line) = context(text, position);
+    if line.trim_start().starts_with('[') {
+        return None;
+    }
+
+    let offset = offset_in_line(line, position.character);
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    let start = line[..offset]
+        .rfind(|c| !is_key_char(c))
+        .map_or(0, |i| i + 1);
+    let end = line[offset..]
+        .find(|c| !is_key_char(c))
+        .map_or(line.len(), |i| offset + i);
+    // only the keys are documented, not the values
+    if line[..start].contains('=') {
+        return None;
+    }
+
+    let key = join(&table, &line[start..end]);
+    let config_key = keys.iter().find(|config_key| config_key.key == key)?;
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!(
+                "`{}` ({})\n\n{}",
+                config_key.key,
+                config_key.kind,
+                config_key.description.as_deref().unwrap_or_default()
+            ),
+        }),
+        range: None,
+    })
+}
+
+/// Returns the table the position is in, as a key of [`config_keys`] (e.g. `run[]` for
+/// `[[run]]`), and the line of the position.
+fn context(text: &str, position: Position) -> (String, &str) {
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    let index = (position.line as usize).min(lines.len().saturating_sub(1));
+    let line = lines.get(index).copied().unwrap_or_default();
+
+    let table = lines
+        .iter()
+        .take(index)
+        .rev()
+        .map(|line| line.trim())
+        .find(|line| line.starts_with('['))
+        .map(table_key)
+        .unwrap_or_default();
+    (table, line)
+}
+
+/// Converts a table header to a key of [`config_keys`]: the profiles accept the same keys as the
+/// root, and the names of the tags are placeholders.
+fn table_key(header: &str) -> String {
+    let array = header.starts_with("[[");
+    let path = header.trim_matches(|c| c == '[' || c == ']' || c == ' ');
+    let mut segments: Vec<String> = path
+        .split('.')
+        .map(|segment| segment.trim().trim_matches('"').to_string())
+        .collect();
+
+    if segments.first().map(String::as_str) == Some("profile") && segments.len() >= 2 {
+        segments.drain(..2);
+    }
+    let len = segments.len();
+    match segments.as_mut_slice() {
+        [table, name, ..] if table == "tag" || table == "template" => *name = "<name>".into(),
+        [run, ..] if run == "run" && (array || len > 1) => *run = "run[]".into(),
+        _ => {}
+    }
+    segments.join(".")
+}
+
+fn join(table: &str, key: &str) -> String {
+    if table.is_empty() {
+        key.to_string()
+    } else {
+        format!("{table}.{key}")
+    }
+}
+
+fn is_toml(uri: &Url) -> bool {
+    let path = Path::new(uri.path());
+    let path = if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("md"))
+    {
+        path.with_extension("")
+    } else {
+        path.to_owned()
+    };
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
+
+/// Converts an offset to a position, whose character is counted in UTF-16 code units.
+fn position_of(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let before = text.get(..offset).unwrap_or_default();
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(
+        u32::try_from(line).unwrap_or(u32::MAX),
+        u32::try_from(character).unwrap_or(u32::MAX),
+    )
+}
+
+/// Converts a character counted in UTF-16 code units to an offset in the line.
+fn offset_in_line(line: &str, character: u32) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        if units >= character as usize {
+            return offset;
+        }
+        units += c.len_utf16();
+    }
+    line.len()
+}
@@ -1,41 +1,118 @@
+mod check;
 mod cli;
+mod completions;
 mod config;
+mod diagnostic;
 mod executor;
+mod explain;
+mod exporters;
+mod init;
+mod list;
+mod loaders;
+mod lsp;
+mod man;
+mod overrides;
+mod paths;
 mod processors;
 mod runner;
+mod schema;
+mod table;
+mod tags;
+mod update_docs;
 
 use config::{Command, Config};
 use runner::{Runner, RunnerOptions};
+use std::path::PathBuf;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = cli::Cli::parse();
+    let (mut cli, matches) = cli::Cli::parse();
 
-    // The highest priority is the cli/env config
-    let mut config = cli.config;
+    let (exporter, explain) = match cli.subcommand.take() {
+        Some(cli::Subcommand::Completions { shell }) => {
+            completions::print_completions(&shell);
+            return Ok(());
+        }
+        Some(cli::Subcommand::Init {
+            dir,
+            literate,
+            force,
+            stdout,
+        }) => {
+            return init::init(&dir, literate, force, stdout).await;
+        }
+        Some(cli::Subcommand::Lsp) => {
+            return lsp::run();
+        }
+        Some(cli::Subcommand::Man { markdown }) => {
+            return if markdown {
+                man::print_markdown()
+            } else {
+                man::print_man()
+            };
+        }
+        Some(cli::Subcommand::CompleteTags) => return completions::print_tags(cli.file).await,
+        Some(cli::Subcommand::CompleteNames) => return completions::print_names(cli.file).await,
+        Some(cli::Subcommand::Explain) => (None, true),
+        Some(cli::Subcommand::Export { exporter }) => (Some(exporter), false),
+        None => (None, false),
+    };
 
-    // Then comes the config file
-    if let Some(file) = cli.file {
-        config.merge(Config::load(file).await?);
-    } else if cli.commands.is_empty() {
-        config.merge(Config::load("run.toml").await?);
+    if cli.command_print_schema {
+        serde_json::to_writer_pretty(std::io::stdout(), &schema::config_schema())?;
+        return Ok(());
     }
 
-    // The defaults are the lowest priority but don't need to be merged. As they are actually
-    // resolved in RunnerOptions::try_from.
+    let resolved = match resolve(&cli, explain).await {
+        Ok(resolved) => resolved,
+        Err(err) if cli.command_check => return check::load_error(&err, &cli.list_format),
+        Err(err) => return Err(err),
+    };
+    let Resolved {
+        config,
+        warnings,
+        cli_config,
+        overrides,
+        layers,
+    } = resolved;
 
-    // Append all the cli commands
-    for command in cli.commands {
-        config.runs.push(Command {
-            command_cmd: shell_words::split(&command)?,
-            ..Default::default()
-        });
+    if cli.command_check {
+        return check::check(config, warnings, &cli.list_format);
+    }
+    // e.g. the keys of a compose file which are ignored
+    for warning in &warnings {
+        eprintln!("warning: {warning}");
     }
 
-    let options = RunnerOptions::try_from(config)?;
+    let runs = layers.as_ref().map(|_| config.runs.clone());
+    let mut options = RunnerOptions::try_from(config)?;
+    // a misspelled name would otherwise silently run nothing
+    if let Some(name) = cli
+        .names
+        .iter()
+        .find(|name| !options.commands.iter().any(|cmd| &cmd.name == *name))
+    {
+        anyhow::bail!("invalid --name option, there is no run named `{name}`");
+    }
+    options.names.clone_from(&cli.names);
 
-    if cli.command_check {
-        return Ok(());
+    if let Some(exporter) = exporter {
+        return exporters::export(options, exporter);
+    }
+
+    if let (Some((file, user)), Some(runs)) = (layers, runs) {
+        let layers = explain::Layers {
+            cli: cli_config,
+            file,
+            user,
+            overrides,
+            runs,
+        };
+        return explain::explain(&options, &layers, &matches);
+    }
+
+    if cli.command_list {
+        return list::list(options, &cli.list_format, &cli.list_group_by);
     }
 
     if cli.command_print_options {
@@ -43,6 +120,80 @@ async fn main() -> anyhow::Result<()> {
         return Ok(());
     }
 
+    if cli.command_update_docs {
+        return update_docs::update_docs(options).await;
+    }
+
     let runner = Runner::new(options);
     runner.run().await
 }
+
+/// A config file along with its path, if any.
+type Loaded = Option<(PathBuf, Config)>;
+
+/// The config merged from all its layers, with the warnings of its loader.
+struct Resolved {
+    config: Config,
+    warnings: Vec<String>,
+    cli_config: Config,
+    overrides: overrides::Overrides,
+    /// The config file and the user config kept apart, to explain where each option comes from
+    layers: Option<(Loaded, Loaded)>,
+}
+
+/// Merges the layers of the config, any of them failing to load being reported by `--check`.
+async fn resolve(cli: &cli::Cli, explain: bool) -> anyhow::Result<Resolved> {
+    // The highest priority is the --set overrides, then the cli/env config
+    let overrides = overrides::Overrides::parse(&cli.sets)?;
+    let cli_config = cli.config.clone();
+    let mut config = cli_config.clone();
+
+    // Then comes the active profile, and the rest of the config file
+    let file = cli
+        .file
+        .clone()
+        .or_else(|| cli.commands.is_empty().then(|| Config::default_path(".")));
+    let (loaded, warnings) = match file {
+        Some(file) => {
+            let (loaded, warnings) = Config::load(&file).await?;
+            (Some((file, loaded)), warnings)
+        }
+        None => (None, vec![]),
+    };
+    // And the user config is the lowest priority
+    let user = if cli.no_user_config {
+        None
+    } else {
+        Config::load_user().await?
+    };
+    // explain needs the layers apart to tell where each option comes from
+    let layers = explain.then(|| (loaded.clone(), user.clone()));
+    let mut loaded = loaded.map(|(_, loaded)| loaded).unwrap_or_default();
+    if let Some((_, user)) = user {
+        loaded.merge(user);
+    }
+    config.merge_with_profile(loaded)?;
+
+    // The defaults are the lowest priority but don't need to be merged. As they are actually
+    // resolved in RunnerOptions::try_from.
+
+    // Append all the cli commands
+    for command in &cli.commands {
+        config.runs.push(Command {
+            command_cmd: shell_words::split(command)?,
+            ..Default::default()
+        });
+    }
+
+    // The cli/env options also take precedence over the options of the runs and of the tags
+    config.merge_into_runs(&cli_config);
+    overrides.apply(&mut config)?;
+
+    Ok(Resolved {
+        config,
+        warnings,
+        cli_config,
+        overrides,
+        layers,
+    })
+}
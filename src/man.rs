@@ -0,0 +1,136 @@
+use crate::cli::Cli;
+use crate::schema::{config_keys, ConfigKey};
+use clap::CommandFactory;
+use clap_mangen::roff::{bold, roman, Roff};
+use clap_mangen::Man;
+use std::fmt::Write;
+
+/// Prints the man page of the CLI, with the environment variables and the config file keys. Both
+/// are derived from the definitions of the CLI and of the config, so that they never drift.
+pub fn print_man() -> anyhow::Result<()> {
+    let cmd = Cli::command();
+    let man = Man::new(cmd.clone());
+    let mut out = std::io::stdout().lock();
+
+    man.render_title(&mut out)?;
+    man.render_name_section(&mut out)?;
+    man.render_synopsis_section(&mut out)?;
+    man.render_description_section(&mut out)?;
+    man.render_options_section(&mut out)?;
+    man.render_subcommands_section(&mut out)?;
+
+    let mut roff = Roff::new();
+    roff.control("SH", ["ENVIRONMENT"]);
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        if let Some(env) = arg.get_env() {
+            roff.control("TP", []);
+            roff.text([bold(env.to_string_lossy())]);
+            roff.text([roman(format!(
+                "Same as {}",
+                arg.get_long()
+                    .map_or_else(String::new, |long| format!("--{long}"))
+            ))]);
+        }
+    }
+
+    roff.control("SH", ["CONFIG FILE"]);
+    roff.text([roman(
+        "The config file (run.toml, run.yaml, run.yml or run.json) accepts the following keys.",
+    )]);
+    for ConfigKey {
+        key,
+        kind,
+        description,
+    } in config_keys()
+    {
+        roff.control("TP", []);
+        roff.text([bold(key), roman(format!(" ({kind})"))]);
+        roff.text([roman(description.unwrap_or_default())]);
+    }
+    roff.to_writer(&mut out)?;
+
+    man.render_version_section(&mut out)?;
+    Ok(())
+}
+
+/// Prints the same reference as the man page, in markdown.
+pub fn print_markdown() -> anyhow::Result<()> {
+    let mut cmd = Cli::command();
+    let mut md = String::new();
+
+    writeln!(md, "# {}", cmd.get_name())?;
+    if let Some(about) = cmd.get_about() {
+        writeln!(md, "\n{}", about.to_string().trim())?;
+    }
+    writeln!(md, "\n## Usage\n\n```\n{}\n```", cmd.render_usage())?;
+
+    writeln!(md, "\n## Subcommands\n")?;
+    writeln!(md, "| Subcommand | Description |\n| --- | --- |")?;
+    for subcommand in cmd.get_subcommands().filter(|cmd| !cmd.is_hide_set()) {
+        writeln!(
+            md,
+            "| `{}` | {} |",
+            subcommand.get_name(),
+            escape(
+                &subcommand
+                    .get_about()
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            )
+        )?;
+    }
+
+    writeln!(md, "\n## Options\n")?;
+    writeln!(
+        md,
+        "| Option | Environment variable | Description |\n| --- | --- | --- |"
+    )?;
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let flag = match (arg.get_short(), arg.get_long()) {
+            (Some(short), Some(long)) => format!("-{short}, --{long}"),
+            (None, Some(long)) => format!("--{long}"),
+            (Some(short), None) => format!("-{short}"),
+            (None, None) => continue, // positional arguments are in the usage
+        };
+        let value = match arg.get_value_names() {
+            Some(names) if arg.get_action().takes_values() => {
+                names.iter().fold(String::new(), |mut value, name| {
+                    let _ = write!(value, " <{name}>");
+                    value
+                })
+            }
+            _ => String::new(),
+        };
+        let env = arg
+            .get_env()
+            .map(|env| format!("`{}`", env.to_string_lossy()))
+            .unwrap_or_default();
+        let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+        writeln!(
+            md,
+            "| `{}` | {env} | {} |",
+            escape(&format!("{flag}{value}")),
+            escape(&help)
+        )?;
+    }
+
+    writeln!(md, "\n## Config file\n")?;
+    writeln!(md, "| Key | Type | Description |\n| --- | --- | --- |")?;
+    for key in config_keys() {
+        writeln!(
+            md,
+            "| `{}` | {} | {} |",
+            key.key,
+            escape(&key.kind),
+            escape(&key.description.unwrap_or_default())
+        )?;
+    }
+
+    print!("{md}");
+    Ok(())
+}
+
+/// Escapes the pipes of a table cell, even in code spans.
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
@@ -0,0 +1,166 @@
+use crate::config::{Command, Config};
+use crate::schema::{config_keys, ConfigKey};
+use anyhow::{bail, Context};
+use merge::Merge;
+use toml::{Table, Value};
+
+/// The `--set` options, which override any key of the config by its dotted path. They are the
+/// highest priority layer, above the command line flags.
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// The overrides of the top-level keys, tags included
+    pub global: Config,
+    /// The overrides of the runs, by name
+    pub runs: Vec<(String, Command)>,
+}
+
+impl Overrides {
+    /// Parses the `KEY=VALUE` options. The values are parsed as TOML values (e.g. `true`, `4000`
+    /// or `["a", "b"]`), falling back to strings, and are then type-checked against the config
+    /// format. The values of the string keys are kept as is (e.g. `tmux.session_prefix=123`).
+    /// Setting `env.KEY` appends the variable to the list of the table.
+    pub fn parse(sets: &[String]) -> anyhow::Result<Self> {
+        let schema = config_keys();
+        let mut global = Table::new();
+        let mut runs: Vec<(String, Table)> = vec![];
+        for set in sets {
+            let (path, value) = set
+                .split_once('=')
+                .with_context(|| format!("invalid --set {set}, expected KEY=VALUE"))?;
+            let keys: Vec<&str> = path.split('.').collect();
+            if keys.iter().any(|key| key.is_empty()) {
+                bail!("invalid --set {set}, the key `{path}` is not a dotted path");
+            }
+            match keys.as_slice() {
+                ["profile", ..] => bail!(
+                    "invalid --set {set}, the profiles cannot be overridden, select the profile and override its options instead"
+                ),
+                ["run", name, keys @ ..] if !keys.is_empty() => {
+                    if !runs.iter().any(|(run, _)| run == name) {
+                        runs.push(((*name).to_string(), Table::new()));
+                    }
+                    let value = parse_value(&schema, &format!("run[].{}", keys.join(".")), value);
+                    for (_, table) in runs.iter_mut().filter(|(run, _)| run == name) {
+                        insert(table, keys, &value)
+                            .with_context(|| format!("invalid --set {set}"))?;
+                    }
+                }
+                ["run", ..] => {
+                    bail!("invalid --set {set}, the runs are overridden with run.NAME.KEY=VALUE")
+                }
+                keys => {
+                    let value = parse_value(&schema, &schema_key(keys), value);
+                    insert(&mut global, keys, &value)
+                        .with_context(|| format!("invalid --set {set}"))?;
+                }
+            }
+        }
+
+        let global = deserialize(global).context("invalid --set option")?;
+        let runs = runs
+            .into_iter()
+            .map(|(name, table)| {
+                let command = deserialize(table)
+                    .with_context(|| format!("invalid --set option for the `{name}` run"))?;
+                Ok((name, command))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { global, runs })
+    }
+
+    /// Applies the overrides on top of the merged config, the runs and their tags included. The
+    /// overrides of a run take precedence over the global ones, and the runs are matched by their
+    /// name, which defaults to their program, possibly inherited from their template.
+    pub fn apply(&self, config: &mut Config) -> anyhow::Result<()> {
+        config.merge_into_runs(&self.global);
+        for (name, overrides) in &self.runs {
+            let mut found = false;
+            for run in &mut config.runs {
+                let extended = run.clone().extend_templates(&config.templates);
+                let run_name = match &extended {
+                    Ok(run) => run.command_name.as_ref().or(run.command_cmd.first()),
+                    // the missing templates are reported when resolving the runs
+                    Err(_) => run.command_name.as_ref().or(run.command_cmd.first()),
+                };
+                if run_name != Some(name) {
+                    continue;
+                }
+                found = true;
+                let mut command = overrides.clone();
+                command.merge(std::mem::take(run));
+                *run = command;
+            }
+            if !found {
+                bail!("invalid --set option, there is no run named `{name}`");
+            }
+        }
+
+        let mut global = self.global.clone();
+        global.merge(std::mem::take(config));
+        *config = global;
+        Ok(())
+    }
+}
+
+/// The key of the config format matching the dotted path of a global override, the names of the
+/// tags and templates being generic (e.g. `tag.<name>.workdir`).
+fn schema_key(keys: &[&str]) -> String {
+    match keys {
+        [table @ ("tag" | "template"), _, keys @ ..] => {
+            format!("{table}.<name>.{}", keys.join("."))
+        }
+        keys => keys.join("."),
+    }
+}
+
+fn insert(table: &mut Table, keys: &[&str], value: &Value) -> anyhow::Result<()> {
+    match keys {
+        [] => Ok(()),
+        // the variables are lists of KEY=VALUE, which keep the raw value
+        ["env", name] => match table.entry("env").or_insert_with(|| Value::Array(vec![])) {
+            Value::Array(envs) => {
+                let value = value.as_str().context("the variables are strings")?;
+                envs.push(Value::String(format!("{name}={value}")));
+                Ok(())
+            }
+            _ => bail!("`env` is already set to a value which is not a list"),
+        },
+        [key] => {
+            table.insert((*key).to_string(), value.clone());
+            Ok(())
+        }
+        [key, keys @ ..] => match table
+            .entry(*key)
+            .or_insert_with(|| Value::Table(Table::new()))
+        {
+            Value::Table(table) => insert(table, keys, value),
+            _ => bail!("`{key}` is already set to a value which is not a table"),
+        },
+    }
+}
+
+/// Type-checks the overrides against the config format.
+fn deserialize<T: serde::de::DeserializeOwned>(table: Table) -> anyhow::Result<T> {
+    Value::Table(table)
+        .try_into()
+        .map_err(|err: toml::de::Error| anyhow::anyhow!(err.to_string().trim_end().to_string()))
+}
+
+/// Parses a value as a TOML value, or as a string when it is not valid TOML (e.g. `api-`) or when
+/// the key is a string. The environment variables keep their raw value too.
+fn parse_value(schema: &[ConfigKey], key: &str, value: &str) -> Value {
+    let variable = key
+        .rsplit_once('.')
+        .is_some_and(|(parent, _)| parent.ends_with("env"));
+    let string = schema
+        .iter()
+        .any(|config_key| config_key.key == key && config_key.kind == "string");
+    if variable || string {
+        return Value::String(value.to_string());
+    }
+    format!("value = {value}")
+        .parse::<Table>()
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| Value::String(value.to_string()))
+}
@@ -0,0 +1,63 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Returns `path` relative to `base`, going up with `..` when it is not within `base`. The paths
+/// which cannot be relative to `base` (e.g. on another drive) are kept as is.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let components: Vec<_> = path.components().collect();
+    let base_components: Vec<_> = base.components().collect();
+    let common = components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(component, base)| component == base)
+        .count();
+    if common == 0 {
+        return path.to_owned();
+    }
+
+    let mut relative: PathBuf = std::iter::repeat_n("..", base_components.len() - common).collect();
+    relative.extend(components.iter().skip(common));
+    if relative.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        relative
+    }
+}
+
+/// Returns the root of the git repository `dir` is in, if any.
+pub fn repository_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        // `.git` is a file in the worktrees and the submodules
+        .find(|dir| dir.join(".git").exists())
+        .map(Path::to_owned)
+}
+
+/// Finds a program as a shell would: an absolute path is only checked to be executable, a name is
+/// searched in the directories of `path`.
+pub fn find_program(program: &Path, path: Option<&OsStr>) -> Option<PathBuf> {
+    if program.is_absolute() {
+        return executable(program);
+    }
+    std::env::split_paths(path?).find_map(|dir| executable(&dir.join(program)))
+}
+
+#[cfg(unix)]
+fn executable(path: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .then(|| path.to_owned())
+}
+
+/// The programs are found with or without their extension on Windows (e.g. `node.exe`).
+#[cfg(not(unix))]
+fn executable(path: &Path) -> Option<PathBuf> {
+    std::iter::once(path.to_owned())
+        .chain(["exe", "cmd", "bat"].map(|extension| {
+            let mut path = path.as_os_str().to_owned();
+            path.push(".");
+            path.push(extension);
+            PathBuf::from(path)
+        }))
+        .find(|path| path.is_file())
+}
@@ -1,58 +1,32 @@
+use crate::config::Source;
 use crate::executor::Executor;
+use crate::paths::{find_program, relative_path};
 use crate::processors;
+use crate::tags::TagFilter;
 use anyhow::Context;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use itertools::Itertools;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::ffi::OsStr;
-use std::path::PathBuf;
+use std::ffi::{OsStr, OsString};
+use std::path::{Component, Path, PathBuf};
 use std::process::ExitStatus;
 use tokio::process::Command;
 
 pub struct Runner {
     commands: Vec<RunnerCommand>,
-    log: RunnerLog,
     mode: RunnerMode,
-    openai: RunnerOpenai,
-    prefix: RunnerPrefix,
     tmux: RunnerTmux,
 }
 
 impl Runner {
     pub fn new(options: RunnerOptions) -> Self {
-        let tags_priority: HashMap<String, usize> = options
-            .tags
-            .into_iter()
-            .enumerate()
-            .rev()
-            .map(|(i, tag)| (tag, i))
-            .collect();
-
-        let commands = if tags_priority.is_empty() {
-            options.commands
-        } else {
-            options
-                .commands
-                .into_iter()
-                .filter(|cmd| {
-                    !cmd.tags.is_empty() && cmd.tags.iter().any(|t| tags_priority.contains_key(t))
-                })
-                .sorted_by(|a, b| {
-                    let a_tags = a.tags.iter().filter_map(|t| tags_priority.get(t));
-                    let b_tags = b.tags.iter().filter_map(|t| tags_priority.get(t));
-                    a_tags.cmp(b_tags)
-                })
-                .collect()
-        };
+        let commands = filter_commands(options.commands, &options.tags, &options.names);
 
         Self {
             commands,
-            log: options.log,
             mode: options.mode,
-            openai: options.openai,
-            prefix: options.prefix,
             tmux: options.tmux,
         }
     }
@@ -97,17 +71,37 @@ impl Runner {
         }
 
         for (i, cmd) in self.commands.iter().enumerate() {
-            let workdir = &cmd.workdir.to_string_lossy();
-            let cmd_str = &format!("{}; read", cmd.to_command_line());
+            let workdir = cmd.workdir.to_string_lossy().into_owned();
+            // the shell of the pane could reset the PATH at startup, so it is prepended to by the
+            // command line itself
+            let cmd_str = match cmd.shell_path() {
+                Some(path) => format!("{path} {}; read", cmd.to_command_line()),
+                None => format!("{}; read", cmd.to_command_line()),
+            };
 
             // create the pane
-            if i == 0 {
-                self.tmux(["new-session", "-s", &session, "-d", "-c", workdir, cmd_str])
-                    .await?;
+            let mut args: Vec<String> = if i == 0 {
+                vec![
+                    "new-session".into(),
+                    "-s".into(),
+                    session.clone(),
+                    "-d".into(),
+                ]
             } else {
-                self.tmux(["split-window", "-t", &session, "-v", "-c", workdir, cmd_str])
-                    .await?;
+                vec![
+                    "split-window".into(),
+                    "-t".into(),
+                    session.clone(),
+                    "-v".into(),
+                ]
+            };
+            // the panes are spawned by the tmux server, which does not inherit the variables
+            for (key, value) in &cmd.envs {
+                args.push("-e".into());
+                args.push(format!("{key}={value}"));
             }
+            args.extend(["-c".into(), workdir, cmd_str]);
+            self.tmux(args).await?;
 
             // set pane title
             self.tmux(["select-pane", "-t", &session, "-T", &cmd.name])
@@ -181,7 +175,7 @@ impl Runner {
         if let RunnerOpenai::Enabled {
             api_base_url,
             api_key,
-        } = &self.openai
+        } = &cmd.openai
         {
             executor.push_err(processors::Openai::new(
                 api_base_url.clone(),
@@ -189,20 +183,20 @@ impl Runner {
             ));
         }
 
-        if let RunnerPrefix::Enabled = self.prefix {
+        if let RunnerPrefix::Enabled = cmd.prefix {
             executor.push_out(processors::Prefix::new(prefix.clone()));
             executor.push_err(processors::Prefix::new(prefix.clone()));
         }
 
-        if self.log.spawns {
+        if cmd.log.spawns {
             eprintln!("{}", Self::format_spawn(&prefix, &cmd.program, &cmd.args));
         }
 
         let status = executor
-            .exec(&cmd.program, &cmd.args, &cmd.workdir, cmd.envs.clone())
+            .exec(&cmd.program, &cmd.args, &cmd.workdir, cmd.spawn_envs())
             .await?;
 
-        if self.log.terminations {
+        if cmd.log.terminations {
             eprintln!(
                 "{}",
                 Self::format_termination(&prefix, &cmd.program, &cmd.args, status)
@@ -236,15 +230,52 @@ impl Runner {
     }
 }
 
+/// Only keeps the commands matching at least one of the tags (all the commands are kept when no
+/// tags are given), ordered by the position of their first matching tag.
+/// Keeps the commands matching the tags, and named after one of the names if any is given, in
+/// the order of the tags.
+pub fn filter_commands(
+    commands: Vec<RunnerCommand>,
+    tags: &TagFilter,
+    names: &[String],
+) -> Vec<RunnerCommand> {
+    let commands = commands
+        .into_iter()
+        .filter(|cmd| names.is_empty() || names.contains(&cmd.name));
+    if tags.is_empty() {
+        return commands.collect();
+    }
+
+    let tags_priority: HashMap<&str, usize> = tags
+        .priorities()
+        .into_iter()
+        .enumerate()
+        .rev()
+        .map(|(i, tag)| (tag, i))
+        .collect();
+
+    commands
+        .filter(|cmd| tags.matches(&cmd.tags))
+        .sorted_by(|a, b| {
+            let a_tags = a.tags.iter().filter_map(|t| tags_priority.get(t.as_str()));
+            let b_tags = b.tags.iter().filter_map(|t| tags_priority.get(t.as_str()));
+            a_tags.cmp(b_tags)
+        })
+        .collect()
+}
+
 #[derive(Debug, Serialize)]
 pub struct RunnerOptions {
     pub commands: Vec<RunnerCommand>,
-    pub log: RunnerLog,
     pub mode: RunnerMode,
-    pub openai: RunnerOpenai,
-    pub prefix: RunnerPrefix,
-    pub tags: Vec<String>,
+    /// The names of the runs selected with `--name`, all of them when empty
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    pub tags: TagFilter,
     pub tmux: RunnerTmux,
+    /// The base working directory, which the exporters write the working directories relative to
+    #[serde(skip)]
+    pub workdir: PathBuf,
 }
 
 #[derive(Debug, Serialize)]
@@ -253,13 +284,85 @@ pub struct RunnerCommand {
     pub args: Vec<String>,
     pub description: Option<String>,
     pub envs: Vec<(String, String)>,
+    pub log: RunnerLog,
     pub name: String,
+    pub openai: RunnerOpenai,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<PathBuf>,
+    pub prefix: RunnerPrefix,
+    #[serde(skip)]
+    pub source: Option<Source>,
     pub tags: Vec<String>,
     pub workdir: PathBuf,
 }
 
 impl RunnerCommand {
-    fn to_command_line(&self) -> String {
+    /// The environment variables to spawn the command with: its own ones, and the PATH it inherits
+    /// (or sets) with its `path` directories prepended.
+    pub fn spawn_envs(&self) -> Vec<(String, String)> {
+        let mut envs = self.envs.clone();
+        if self.path.is_empty() {
+            return envs;
+        }
+
+        let inherited = envs
+            .iter()
+            .rev()
+            .find(|(key, _)| key == "PATH")
+            .map(|(_, value)| OsString::from(value))
+            .or_else(|| std::env::var_os("PATH"));
+        let mut dirs = self.path.clone();
+        dirs.extend(inherited.iter().flat_map(std::env::split_paths));
+        if let Ok(path) = std::env::join_paths(dirs) {
+            envs.push(("PATH".into(), path.to_string_lossy().into_owned()));
+        }
+        envs
+    }
+
+    /// Finds the program like the command would be spawned: a relative path from the working
+    /// directory, a name from the PATH of the command.
+    pub fn find_program(&self) -> Option<PathBuf> {
+        let program = Path::new(&self.program);
+        let is_relative_path = !program.is_absolute()
+            && (self.program.contains('/') || self.program.contains(std::path::MAIN_SEPARATOR));
+        if is_relative_path {
+            // without the `.` components, e.g. of `./bin/serve`
+            let program: PathBuf = self
+                .workdir
+                .join(program)
+                .components()
+                .filter(|component| *component != Component::CurDir)
+                .collect();
+            return find_program(&program, None);
+        }
+        let path = self
+            .spawn_envs()
+            .into_iter()
+            .rev()
+            .find(|(key, _)| key == "PATH")
+            .map(|(_, value)| OsString::from(value))
+            .or_else(|| std::env::var_os("PATH"));
+        find_program(program, path.as_deref())
+    }
+
+    /// The shell assignment prepending the `path` directories to the PATH, relative to the working
+    /// directory of the command (e.g. `PATH=node_modules/.bin:"$PATH"`).
+    pub fn shell_path(&self) -> Option<String> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let dirs: Vec<_> = self
+            .path
+            .iter()
+            .map(|dir| {
+                shell_words::quote(&relative_path(dir, &self.workdir).to_string_lossy())
+                    .into_owned()
+            })
+            .collect();
+        Some(format!("PATH={}:\"$PATH\"", dirs.join(":")))
+    }
+
+    pub fn to_command_line(&self) -> String {
         let mut args = vec![];
 
         args.push(self.program.as_str());
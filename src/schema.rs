@@ -0,0 +1,212 @@
+use crate::config::Config;
+use clap::CommandFactory;
+use schemars::gen::SchemaSettings;
+use schemars::schema::{RootSchema, Schema, SchemaObject};
+
+/// Generates the JSON Schema of the config file format. The descriptions are pulled from the help
+/// of the matching CLI flags, so that both never drift apart.
+pub fn config_schema() -> RootSchema {
+    // TOML has no null, unset options are simply omitted
+    let mut root = SchemaSettings::draft07()
+        .with(|settings| settings.option_add_null_type = false)
+        .into_generator()
+        .into_root_schema_for::<Config>();
+    let cmd = Config::command();
+
+    describe_properties(&mut root.schema, None, &cmd);
+    for (name, definition) in &mut root.definitions {
+        if let Schema::Object(definition) = definition {
+            // profiles are nested configs
+            let section = Some(name.to_lowercase()).filter(|section| section != "config");
+            describe_properties(definition, section.as_deref(), &cmd);
+        }
+    }
+
+    root
+}
+
+/// The CLI flag of a config key is its dotted path, using dashes instead of dots and underscores
+/// (e.g. `tmux.session_prefix` is `--tmux-session-prefix`).
+fn describe_properties(schema: &mut SchemaObject, section: Option<&str>, cmd: &clap::Command) {
+    let Some(object) = schema.object.as_mut() else {
+        return;
+    };
+
+    for (key, property) in &mut object.properties {
+        let Schema::Object(property) = property else {
+            continue;
+        };
+
+        let long = match section {
+            Some(section) => format!("{section}-{key}"),
+            None => key.clone(),
+        }
+        .replace('_', "-");
+
+        let help = cmd
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(long.as_str()))
+            .and_then(clap::Arg::get_help);
+
+        // explicit descriptions take precedence
+        if let (Some(help), None) = (help, &property.metadata().description) {
+            property.metadata().description = Some(help.to_string());
+        }
+
+        if property
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.default.as_ref())
+            .is_some_and(is_unset)
+        {
+            property.metadata().default = None;
+        }
+    }
+}
+
+fn is_unset(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => true,
+        serde_json::Value::Object(object) => object.values().all(is_unset),
+        _ => false,
+    }
+}
+
+/// A key of the config file format, as documented in the references.
+pub struct ConfigKey {
+    pub key: String,
+    pub kind: String,
+    pub description: Option<String>,
+}
+
+/// Lists all the keys of the config file format, as dotted paths (e.g. `run[].log.enabled`).
+/// Profiles are not expanded, as they accept the same keys as the top-level.
+pub fn config_keys() -> Vec<ConfigKey> {
+    let root = config_schema();
+    let mut keys = vec![];
+    collect_keys(&root, &root.schema, "", &mut keys);
+    keys
+}
+
+fn collect_keys(root: &RootSchema, schema: &SchemaObject, prefix: &str, keys: &mut Vec<ConfigKey>) {
+    let Some(object) = schema.object.as_ref() else {
+        return;
+    };
+
+    for (name, property) in &object.properties {
+        let Schema::Object(property) = property else {
+            continue;
+        };
+        let key = format!("{prefix}{name}");
+        let description = property
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.description.clone());
+
+        // nested tables are flattened, except for profiles which are whole configs
+        if let Some(definition) = referenced_definition(root, property) {
+            if definition.object.is_some() {
+                collect_keys(root, definition, &format!("{key}."), keys);
+                continue;
+            }
+        }
+        if let Some((item_key, definition)) = property
+            .array
+            .as_ref()
+            .and_then(|array| array.items.as_ref())
+            .and_then(|items| match items {
+                schemars::schema::SingleOrVec::Single(item) => Some(item),
+                schemars::schema::SingleOrVec::Vec(_) => None,
+            })
+            .and_then(|item| match &**item {
+                Schema::Object(item) => referenced_definition(root, item),
+                Schema::Bool(_) => None,
+            })
+            .map(|definition| (format!("{key}[]."), definition))
+        {
+            collect_keys(root, definition, &item_key, keys);
+            continue;
+        }
+        if let Some(Schema::Object(value)) = property
+            .object
+            .as_ref()
+            .and_then(|object| object.additional_properties.as_deref())
+        {
+            if value.reference.as_deref() != Some("#/definitions/Config") {
+                if let Some(definition) = referenced_definition(root, value) {
+                    collect_keys(root, definition, &format!("{key}.<name>."), keys);
+                    continue;
+                }
+            }
+            keys.push(ConfigKey {
+                key: format!("{key}.<name>"),
+                kind: "table".into(),
+                description,
+            });
+            continue;
+        }
+
+        keys.push(ConfigKey {
+            key,
+            kind: describe_kind(root, property),
+            description,
+        });
+    }
+}
+
+/// Resolves `$ref` and `allOf: [{ $ref }]` to the referenced definition.
+fn referenced_definition<'a>(
+    root: &'a RootSchema,
+    schema: &SchemaObject,
+) -> Option<&'a SchemaObject> {
+    let reference = schema.reference.as_deref().or_else(|| {
+        schema
+            .subschemas
+            .as_ref()
+            .and_then(|subschemas| subschemas.all_of.as_ref())
+            .and_then(|all_of| all_of.first())
+            .and_then(|schema| match schema {
+                Schema::Object(schema) => schema.reference.as_deref(),
+                Schema::Bool(_) => None,
+            })
+    })?;
+
+    match root
+        .definitions
+        .get(reference.strip_prefix("#/definitions/")?)
+    {
+        Some(Schema::Object(definition)) => Some(definition),
+        _ => None,
+    }
+}
+
+fn describe_kind(root: &RootSchema, schema: &SchemaObject) -> String {
+    let schema = referenced_definition(root, schema).unwrap_or(schema);
+
+    if let Some(values) = &schema.enum_values {
+        return values
+            .iter()
+            .filter_map(serde_json::Value::as_str)
+            .collect::<Vec<_>>()
+            .join("|");
+    }
+
+    let kind = |schema: &SchemaObject| match &schema.instance_type {
+        Some(schemars::schema::SingleOrVec::Single(kind)) => serde_json::to_value(kind)
+            .ok()
+            .and_then(|kind| kind.as_str().map(String::from)),
+        _ => None,
+    };
+
+    match (kind(schema).as_deref(), &schema.array) {
+        (Some("array"), Some(array)) => match &array.items {
+            Some(schemars::schema::SingleOrVec::Single(item)) => match &**item {
+                Schema::Object(item) => format!("array of {}", describe_kind(root, item)),
+                Schema::Bool(_) => "array".into(),
+            },
+            _ => "array".into(),
+        },
+        (Some(kind), _) => kind.into(),
+        (None, _) => "any".into(),
+    }
+}
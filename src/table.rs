@@ -0,0 +1,40 @@
+use std::fmt::Write;
+
+/// Renders groups of rows as a table under their title, the columns being aligned across all the
+/// groups, e.g.:
+///
+/// ```text
+/// global:
+///   OPTION  VALUE       SOURCE
+///   mode    sequential  default
+/// ```
+pub fn render<const N: usize>(
+    header: [&str; N],
+    groups: &[(String, Vec<[String; N]>)],
+) -> Result<String, std::fmt::Error> {
+    let header = header.map(String::from);
+    let mut widths = header.clone().map(|column| column.len());
+    for row in groups.iter().flat_map(|(_, rows)| rows) {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for (i, (title, rows)) in groups.iter().enumerate() {
+        if i > 0 {
+            table.push('\n');
+        }
+        writeln!(table, "{title}:")?;
+        for row in std::iter::once(&header).chain(rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(column, width)| format!("{column:width$}"))
+                .collect::<Vec<_>>()
+                .join("  ");
+            writeln!(table, "  {}", line.trim_end())?;
+        }
+    }
+    Ok(table)
+}
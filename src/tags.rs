@@ -0,0 +1,234 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// A filter on the tags of the commands, made of comma-separated terms. A command matches when it
+/// matches at least one of the positive terms (if any), and none of the terms starting with `!`.
+///
+/// A term is a boolean expression of tags: `&` is a logical AND, `!` a logical NOT, `,` a logical
+/// OR and parentheses group sub-expressions (e.g. `backend,!slow` or `(api,www)&integration`).
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    terms: Vec<Expr>,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Tag(String),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+impl TagFilter {
+    /// Parses the `--tags` and `--exclude-tags` expressions. The values may have been split on
+    /// commas by the CLI, so they are joined back before being parsed.
+    pub fn parse(tags: &[String], exclude_tags: &[String]) -> anyhow::Result<Self> {
+        let mut terms = parse_terms(&tags.join(","))?;
+        terms.extend(
+            parse_terms(&exclude_tags.join(","))?
+                .into_iter()
+                .map(|term| Expr::Not(Box::new(term))),
+        );
+        Ok(Self { terms })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn matches(&self, tags: &[String]) -> bool {
+        let (excludes, includes): (Vec<_>, Vec<_>) = self
+            .terms
+            .iter()
+            .partition(|term| matches!(term, Expr::Not(_)));
+
+        (includes.is_empty() || includes.iter().any(|term| term.eval(tags)))
+            && excludes.iter().all(|term| term.eval(tags))
+    }
+
+    /// The tags that are not negated, in order of appearance. They give the order in which the
+    /// matching commands are run.
+    pub fn priorities(&self) -> Vec<&str> {
+        let mut tags = vec![];
+        for term in &self.terms {
+            term.collect_positive_tags(&mut tags);
+        }
+        tags
+    }
+
+    /// All the tags of the filter, negated or not, in order of appearance.
+    pub fn tags(&self) -> Vec<&str> {
+        let mut tags = vec![];
+        for term in &self.terms {
+            term.collect_tags(&mut tags);
+        }
+        tags
+    }
+}
+
+impl Serialize for TagFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.terms.iter().map(ToString::to_string))
+    }
+}
+
+impl Expr {
+    fn eval(&self, tags: &[String]) -> bool {
+        match self {
+            Expr::Tag(tag) => tags.contains(tag),
+            Expr::Not(expr) => !expr.eval(tags),
+            Expr::And(exprs) => exprs.iter().all(|expr| expr.eval(tags)),
+            Expr::Or(exprs) => exprs.iter().any(|expr| expr.eval(tags)),
+        }
+    }
+
+    fn collect_positive_tags<'a>(&'a self, tags: &mut Vec<&'a str>) {
+        match self {
+            Expr::Tag(tag) => tags.push(tag),
+            Expr::Not(_) => {}
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_positive_tags(tags);
+                }
+            }
+        }
+    }
+
+    fn collect_tags<'a>(&'a self, tags: &mut Vec<&'a str>) {
+        match self {
+            Expr::Tag(tag) => tags.push(tag),
+            Expr::Not(expr) => expr.collect_tags(tags),
+            Expr::And(exprs) | Expr::Or(exprs) => {
+                for expr in exprs {
+                    expr.collect_tags(tags);
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Tag(tag) => write!(f, "{tag}"),
+            Expr::Not(expr) => match **expr {
+                Expr::Tag(_) | Expr::Not(_) => write!(f, "!{expr}"),
+                _ => write!(f, "!({expr})"),
+            },
+            Expr::And(exprs) => {
+                for (i, expr) in exprs.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "&" };
+                    match expr {
+                        Expr::Or(_) => write!(f, "{separator}({expr})")?,
+                        _ => write!(f, "{separator}{expr}")?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Or(exprs) => {
+                for (i, expr) in exprs.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(f, "{separator}{expr}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses a comma-separated list of terms, an empty input having no terms.
+fn parse_terms(input: &str) -> anyhow::Result<Vec<Expr>> {
+    if input.trim().is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut parser = Parser { input, position: 0 };
+    let expr = parser
+        .parse_or()
+        .and_then(|expr| match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("unexpected `{c}`")),
+        })
+        .map_err(|err| {
+            anyhow::anyhow!(
+                "invalid tag expression `{}`: {} at position {}",
+                input,
+                err,
+                parser.position + 1
+            )
+        })?;
+
+    Ok(match expr {
+        Expr::Or(terms) => terms,
+        term => vec![term],
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.input[self.position..];
+        let trimmed = rest.trim_start();
+        self.position += rest.len() - trimmed.len();
+        trimmed.chars().next()
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.parse_and()?];
+        while self.peek() == Some(',') {
+            self.position += 1;
+            exprs.push(self.parse_and()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::Or(exprs)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut exprs = vec![self.parse_unary()?];
+        while self.peek() == Some('&') {
+            self.position += 1;
+            exprs.push(self.parse_unary()?);
+        }
+        Ok(if exprs.len() == 1 {
+            exprs.remove(0)
+        } else {
+            Expr::And(exprs)
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('!') => {
+                self.position += 1;
+                Ok(Expr::Not(Box::new(self.parse_unary()?)))
+            }
+            Some('(') => {
+                self.position += 1;
+                let expr = self.parse_or()?;
+                if self.peek() != Some(')') {
+                    return Err("expected `)`".into());
+                }
+                self.position += 1;
+                Ok(expr)
+            }
+            _ => {
+                let tag: String = self.input[self.position..]
+                    .chars()
+                    .take_while(|c| !c.is_whitespace() && !",&!()".contains(*c))
+                    .collect();
+                if tag.is_empty() {
+                    return Err("expected a tag".into());
+                }
+                self.position += tag.len();
+                Ok(Expr::Tag(tag))
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use crate::runner::{filter_commands, RunnerCommand, RunnerOptions};
+use anyhow::{bail, Context};
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Executes the commands declared in a literate config file, and writes their stdout in an
+/// ```output code block right after the code block they come from. An existing ```output code
+/// block is replaced, otherwise a new one is inserted. The runs of a config code block are
+/// executed too, their outputs being written together after it. The tags select the commands to
+/// execute, e.g. to leave out the servers which never exit.
+pub async fn update_docs(options: RunnerOptions) -> anyhow::Result<()> {
+    let commands = filter_commands(options.commands, &options.tags, &options.names);
+
+    // the commands passed as CLI arguments are not part of the docs
+    let Some(path) = commands
+        .iter()
+        .filter_map(|cmd| cmd.source.as_ref())
+        .map(|source| &source.path)
+        .find(|path| path.to_string_lossy().ends_with(".md"))
+    else {
+        bail!("no commands found in a literate config file");
+    };
+
+    let markdown = tokio::fs::read_to_string(&path).await?;
+    let code_blocks = find_code_blocks(&markdown);
+
+    let mut outputs: BTreeMap<usize, (Range<usize>, String)> = BTreeMap::new();
+    for cmd in &commands {
+        let Some(source) = cmd.source.as_ref().filter(|source| &source.path == path) else {
+            continue;
+        };
+
+        // the output is written after the code block the command is declared in
+        let Some((span, _)) = code_blocks
+            .iter()
+            .find(|(span, _)| span.contains(&source.span.start))
+        else {
+            continue;
+        };
+
+        let stdout = capture_stdout(cmd)
+            .await
+            .with_context(|| format!("failed to update the output of [{}]", cmd.name))?;
+
+        outputs
+            .entry(span.start)
+            .or_insert_with(|| (span.clone(), String::new()))
+            .1
+            .push_str(&stdout);
+    }
+
+    let output_blocks: Vec<_> = code_blocks
+        .into_iter()
+        .filter_map(|(span, info)| (info == "output").then_some(span))
+        .collect();
+
+    let mut updated = String::with_capacity(markdown.len());
+    let mut cursor = 0;
+    for (span, output) in outputs.into_values() {
+        let existing = output_blocks.iter().find(|block| {
+            block.start >= span.end
+                && markdown
+                    .get(span.end..block.start)
+                    .is_some_and(|between| between.trim().is_empty())
+        });
+
+        let (end, separator) = match existing {
+            Some(block) => (block.start, ""),
+            None => (span.end, "\n\n"),
+        };
+        updated.push_str(markdown.get(cursor..end).unwrap_or_default());
+        updated.push_str(separator);
+        updated.push_str(&format_output_block(&output));
+        cursor = existing.map_or(span.end, |block| block.end);
+    }
+    updated.push_str(markdown.get(cursor..).unwrap_or_default());
+
+    tokio::fs::write(&path, updated)
+        .await
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+async fn capture_stdout(cmd: &RunnerCommand) -> anyhow::Result<String> {
+    let output = Command::new(&cmd.program)
+        .args(&cmd.args)
+        .current_dir(&cmd.workdir)
+        .envs(cmd.spawn_envs())
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "could not spawn {:?} in {}",
+                &cmd.program,
+                cmd.workdir.display()
+            )
+        })?;
+
+    if !output.status.success() {
+        bail!(
+            "{} terminated with {}",
+            cmd.to_command_line(),
+            output.status
+        );
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Returns the span and the info string of all the fenced code blocks.
+fn find_code_blocks(markdown: &str) -> Vec<(Range<usize>, String)> {
+    use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+    pulldown_cmark::Parser::new(markdown)
+        .into_offset_iter()
+        .filter_map(|(event, span)| match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                Some((span, info.into_string()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The fence is made longer than any backtick sequence found in the output, so that the output
+/// cannot close the code block early.
+fn format_output_block(output: &str) -> String {
+    let longest_backticks = output
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_backticks.max(2) + 1);
+
+    let newline = if output.is_empty() || output.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+
+    format!("{fence}output\n{output}{newline}{fence}")
+}
@@ -4,7 +4,9 @@ use glob::glob;
 use pretty_assertions::StrComparison;
 use std::ffi::OsStr;
 use std::path::PathBuf;
+use std::process::Stdio;
 use std::{path::Path, process::Output};
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
 
 lazy_static::lazy_static! {
@@ -36,7 +38,18 @@ async fn run_examples_checks() -> anyhow::Result<()> {
 async fn run_e2e_tests() -> anyhow::Result<()> {
     let mut set = tokio::task::JoinSet::new();
 
-    for (test_name, file) in list_files(["tests/**/*.toml", "tests/**/*.toml.md"]) {
+    for (test_name, file) in list_files([
+        "tests/**/*.toml",
+        "tests/**/*.toml.md",
+        "tests/**/*.yaml",
+        "tests/**/*.yaml.md",
+        "tests/**/*.yml",
+        "tests/**/*.yml.md",
+        "tests/**/*.json",
+        "tests/**/*.json.md",
+        "tests/**/Procfile",
+        "tests/**/justfile",
+    ]) {
         set.spawn(async move {
             e2e_test(&file)
                 .await
@@ -53,8 +66,33 @@ async fn run_e2e_tests() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn run_reference_check() -> anyhow::Result<()> {
+    const HEADER: &str = "<!-- generated with `run man --markdown`, do not edit -->";
+
+    let file =
+        format!("{CARGO_MANIFEST_DIR}/tests/man/it_should_print_the_markdown_reference.toml");
+    let output = exec(&file, ["man", "--markdown"], "", "").await?;
+    let reference = format!("{CARGO_MANIFEST_DIR}/docs/user-guide/reference.md");
+    let reference = tokio::fs::read_to_string(&reference).await?;
+
+    let Some(expected) = reference.strip_prefix(HEADER) else {
+        bail!("the reference does not start with: {HEADER}");
+    };
+    let expected = patch(expected);
+    let actual = patch(std::str::from_utf8(&output.stdout)?);
+    if expected != actual {
+        bail!(
+            "docs/user-guide/reference.md is outdated, regenerate it with `run man --markdown`: {}",
+            StrComparison::new(&expected, &actual)
+        );
+    }
+
+    Ok(())
+}
+
 async fn example_check<P: AsRef<Path>>(file: P) -> anyhow::Result<()> {
-    let output = exec(&file, ["--check"]).await?;
+    let output = exec(&file, ["--check"], "", "").await?;
 
     if !output.status.success() {
         let stderr = std::str::from_utf8(&output.stderr)?;
@@ -66,15 +104,29 @@ async fn example_check<P: AsRef<Path>>(file: P) -> anyhow::Result<()> {
 
 async fn e2e_test<P: AsRef<Path>>(file: P) -> anyhow::Result<()> {
     let args = read_file(&file, ".args").await.unwrap_or_default();
+    let envs = read_file(&file, ".env")
+        .await
+        .map(patch)
+        .unwrap_or_default();
+    // written as is, e.g. for the framed messages of the language server
+    let stdin = read_file(&file, ".stdin").await.unwrap_or_default();
     let expected_stdout = read_file(&file, ".stdout").await.map(patch);
     let expected_stderr = read_file(&file, ".stderr").await.map(patch);
+    // the expected content of the file once run, e.g. for `--update-docs`
+    let expected_file = read_file(&file, ".updated").await.map(patch);
 
-    if expected_stdout.is_none() && expected_stderr.is_none() {
-        bail!("none of .stdout or .stderr found");
+    if expected_stdout.is_none() && expected_stderr.is_none() && expected_file.is_none() {
+        bail!("none of .stdout, .stderr or .updated found");
     }
 
+    // the files which get updated are run from a copy, so that the tests can run again
+    let file = match expected_file {
+        Some(_) => copy_to_tmp(&file).await?,
+        None => file.as_ref().to_owned(),
+    };
+
     // exec and get output
-    let output = exec(&file, args.lines()).await?;
+    let output = exec(&file, args.lines(), &envs, &stdin).await?;
     let stdout = patch(std::str::from_utf8(&output.stdout)?);
     let stderr = patch(std::str::from_utf8(&output.stderr)?);
 
@@ -102,10 +154,35 @@ async fn e2e_test<P: AsRef<Path>>(file: P) -> anyhow::Result<()> {
         }
     }
 
+    // assert the updated file
+    if let Some(expected) = expected_file {
+        let updated = patch(tokio::fs::read_to_string(&file).await?);
+        if expected != updated {
+            bail!(
+                "updated file does not match: {}",
+                StrComparison::new(&expected, &updated)
+            );
+        }
+    }
+
     Ok(())
 }
 
-async fn exec<P, I, S>(file: P, args: I) -> anyhow::Result<Output>
+/// Copies the file to a directory of its own under the temporary directory of the tests, and
+/// returns the path of the copy.
+async fn copy_to_tmp<P: AsRef<Path>>(file: P) -> anyhow::Result<PathBuf> {
+    let file = file.as_ref();
+    let test_name = file.strip_prefix(CARGO_MANIFEST_DIR)?.to_string_lossy();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(test_name.replace(['/', '\\'], "_"));
+    tokio::fs::create_dir_all(&dir).await?;
+    let copy = dir.join(file.file_name().context("no file name")?);
+    tokio::fs::copy(file, &copy).await?;
+    Ok(copy)
+}
+
+/// Runs the CLI on the file, with the environment variables given as KEY=VALUE lines, and `stdin`
+/// written to its standard input.
+async fn exec<P, I, S>(file: P, args: I, envs: &str, stdin: &str) -> anyhow::Result<Output>
 where
     P: AsRef<Path>,
     I: IntoIterator<Item = S>,
@@ -114,13 +191,28 @@ where
     let mut cmd = Command::new(env!("CARGO_BIN_EXE_run"));
 
     cmd.env("PATH", COREUTILS_PATH.to_str().unwrap());
+    // the user config of the machine must not leak into the tests
+    cmd.env(
+        "XDG_CONFIG_HOME",
+        format!("{CARGO_MANIFEST_DIR}/tests/.config"),
+    );
+    cmd.envs(envs.lines().filter_map(|kv| kv.split_once('=')));
 
     cmd.arg("-f");
     cmd.arg(file.as_ref());
 
     cmd.args(args);
 
-    Ok(cmd.output().await?)
+    cmd.stdin(Stdio::piped());
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let mut child = cmd.spawn()?;
+    let mut child_stdin = child.stdin.take().context("no stdin")?;
+    child_stdin.write_all(stdin.as_bytes()).await?;
+    // closes the input of the child
+    drop(child_stdin);
+
+    Ok(child.wait_with_output().await?)
 }
 
 async fn read_file<P: AsRef<Path>>(filepath: P, suffix: &str) -> Option<String> {
@@ -139,6 +231,8 @@ where
         .into_iter()
         .map(|pattern| format!("{CARGO_MANIFEST_DIR}/{pattern}"))
         .flat_map(|pattern| glob(&pattern).unwrap().map(Result::unwrap))
+        // fixtures are inputs of the tests (e.g. project files), not tests themselves
+        .filter(|file| !file.components().any(|c| c.as_os_str() == "fixtures"))
         .map(|file| {
             let test_name = &file.strip_prefix(CARGO_MANIFEST_DIR).unwrap();
             let test_name = test_name.to_str().unwrap().to_string();